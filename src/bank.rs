@@ -0,0 +1,379 @@
+use egui::{pos2, vec2, Id, Rect, Response, Stroke, Ui};
+
+use crate::{
+    drain_all_events, Linkset, Pump, PumpConfig, PumpData, PumpEvent, PumpEventKind,
+    SyringeLibrary, PANEL_SIZE,
+};
+
+/// A collection of pumps shown together in a grid
+///
+/// Pumps are keyed by ```PumpData::uid``` and kept in display order.
+/// The grid fits as many columns as the available width allows
+/// unless a fixed number is set, and pumps can be reordered by
+/// dragging them by their menu bar.
+///
+/// Clicking a pump's background or pump art selects it (the arrows
+/// and menu icons keep their own meaning), ctrl/cmd-click toggles it and
+/// shift-click selects the range from the last clicked pump.
+/// Selection is stored in ```PumpData::selected```.
+pub struct PumpBank {
+    pumps: Vec<PumpData>,
+    anchor: Option<u32>,
+    columns: Option<usize>,
+    reorderable: bool,
+    scale: f32,
+    config: PumpConfig,
+    syringe_library: SyringeLibrary,
+    id_salt: Id,
+}
+
+/// Result of showing a ```PumpBank```
+pub struct PumpBankResponse {
+    /// Union of all pump responses
+    pub response: Response,
+    /// The set of selected pumps changed this frame
+    pub selection_changed: bool,
+    /// A pump was dropped in a new position this frame
+    pub reordered: bool,
+}
+
+impl Default for PumpBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PumpBank {
+    pub fn new() -> Self {
+        PumpBank {
+            pumps: Vec::new(),
+            anchor: None,
+            columns: None,
+            reorderable: true,
+            scale: 1.0,
+            config: PumpConfig::DEFAULT,
+            syringe_library: SyringeLibrary::new(),
+            id_salt: Id::new("egui_widget_pump_bank"),
+        }
+    }
+
+    /// Fixed number of pumps per grid row, instead of fitting the width
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = Some(columns.max(1));
+        self
+    }
+
+    /// Allow drag-and-drop reordering, enabled by default
+    pub fn reorderable(mut self, reorderable: bool) -> Self {
+        self.reorderable = reorderable;
+        self
+    }
+
+    /// Scale applied to every pump, see ```Pump::scale```
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Config applied to every pump
+    pub fn config(mut self, config: PumpConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// User-defined syringes offered by every pump's syringe picker
+    pub fn syringe_library(mut self, library: SyringeLibrary) -> Self {
+        self.syringe_library = library;
+        self
+    }
+
+    /// The library, e.g. to add syringes or save it
+    pub fn syringe_library_mut(&mut self) -> &mut SyringeLibrary {
+        &mut self.syringe_library
+    }
+
+    /// Salt for the grid Id, needed when showing several banks in one ui
+    pub fn id_salt(mut self, id_salt: impl std::hash::Hash) -> Self {
+        self.id_salt = Id::new(id_salt);
+        self
+    }
+
+    /// Add a pump at the end of the bank.
+    ///
+    /// A pump with the same uid is replaced in place and returned.
+    pub fn push(&mut self, pump: PumpData) -> Option<PumpData> {
+        match self.index_of(pump.uid) {
+            Some(index) => Some(std::mem::replace(&mut self.pumps[index], pump)),
+            None => {
+                self.pumps.push(pump);
+                None
+            }
+        }
+    }
+
+    /// Add a pump at ```index``` in display order.
+    ///
+    /// A pump with the same uid is removed first and returned.
+    pub fn insert(&mut self, index: usize, pump: PumpData) -> Option<PumpData> {
+        let old = self.remove(pump.uid);
+        self.pumps.insert(index.min(self.pumps.len()), pump);
+        old
+    }
+
+    pub fn remove(&mut self, uid: u32) -> Option<PumpData> {
+        if self.anchor == Some(uid) {
+            self.anchor = None;
+        }
+        self.index_of(uid).map(|index| self.pumps.remove(index))
+    }
+
+    /// Move pump ```uid``` to ```index``` in display order.
+    pub fn move_to(&mut self, uid: u32, index: usize) {
+        if let Some(from) = self.index_of(uid) {
+            let pump = self.pumps.remove(from);
+            self.pumps.insert(index.min(self.pumps.len()), pump);
+        }
+    }
+
+    /// Position of pump ```uid``` in display order
+    pub fn index_of(&self, uid: u32) -> Option<usize> {
+        self.pumps.iter().position(|pump| pump.uid == uid)
+    }
+
+    pub fn contains(&self, uid: u32) -> bool {
+        self.index_of(uid).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pumps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pumps.is_empty()
+    }
+
+    pub fn get(&self, uid: u32) -> Option<&PumpData> {
+        self.pumps.iter().find(|pump| pump.uid == uid)
+    }
+
+    pub fn get_mut(&mut self, uid: u32) -> Option<&mut PumpData> {
+        self.pumps.iter_mut().find(|pump| pump.uid == uid)
+    }
+
+    /// Pumps in display order
+    pub fn iter(&self) -> std::slice::Iter<'_, PumpData> {
+        self.pumps.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, PumpData> {
+        self.pumps.iter_mut()
+    }
+
+    /// Uids in display order
+    pub fn uids(&self) -> Vec<u32> {
+        self.pumps.iter().map(|pump| pump.uid).collect()
+    }
+
+    /// Pending events from all pumps, pump by pump
+    pub fn events(&self) -> impl Iterator<Item = &PumpEvent> {
+        self.pumps.iter().flat_map(|pump| pump.events.iter())
+    }
+
+    /// Take pending events from all pumps, ordered by time.
+    pub fn drain_events(&mut self) -> Vec<PumpEvent> {
+        drain_all_events(self.pumps.iter_mut())
+    }
+
+    // ==================================================================
+    // Selection
+    // ==================================================================
+
+    pub fn is_selected(&self, uid: u32) -> bool {
+        self.get(uid).is_some_and(|pump| pump.selected)
+    }
+
+    /// Uids of the selected pumps, in display order
+    pub fn selected_uids(&self) -> Vec<u32> {
+        self.selected().map(|pump| pump.uid).collect()
+    }
+
+    pub fn selected(&self) -> impl Iterator<Item = &PumpData> {
+        self.pumps.iter().filter(|pump| pump.selected)
+    }
+
+    pub fn selected_mut(&mut self) -> impl Iterator<Item = &mut PumpData> {
+        self.pumps.iter_mut().filter(|pump| pump.selected)
+    }
+
+    /// Select only ```uid```.
+    pub fn select(&mut self, uid: u32) {
+        for pump in &mut self.pumps {
+            pump.selected = pump.uid == uid;
+        }
+        self.anchor = Some(uid);
+    }
+
+    /// Add ```uid``` to, or remove it from, the selection.
+    pub fn toggle(&mut self, uid: u32) {
+        if let Some(pump) = self.get_mut(uid) {
+            pump.selected = !pump.selected;
+            self.anchor = Some(uid);
+        }
+    }
+
+    /// Select every pump between the last clicked pump and ```uid```.
+    ///
+    /// Selects only ```uid``` if nothing has been clicked yet.
+    pub fn select_range(&mut self, uid: u32) {
+        let anchor = self.anchor.and_then(|anchor| self.index_of(anchor));
+        let (Some(anchor), Some(end)) = (anchor, self.index_of(uid)) else {
+            self.select(uid);
+            return;
+        };
+        let range = anchor.min(end)..=anchor.max(end);
+        for (index, pump) in self.pumps.iter_mut().enumerate() {
+            pump.selected = range.contains(&index);
+        }
+    }
+
+    pub fn select_all(&mut self) {
+        for pump in &mut self.pumps {
+            pump.selected = true;
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        for pump in &mut self.pumps {
+            pump.selected = false;
+        }
+        self.anchor = None;
+    }
+
+    /// Put every selected pump in link group ```linkset```.
+    pub fn assign_linkset(&mut self, linkset: Linkset) {
+        for pump in self.selected_mut() {
+            pump.linkset = linkset;
+        }
+    }
+
+    // ==================================================================
+    // Display
+    // ==================================================================
+
+    /// Show all pumps in a grid.
+    ///
+    /// Updates the selection from clicks and the display order from
+    /// drag-and-drop.
+    pub fn show(&mut self, ui: &mut Ui) -> PumpBankResponse {
+        let selected_before = self.selected_uids();
+        let spacing = ui.spacing().item_spacing;
+
+        // Shrink to fit a single column if need be, then fit as many columns as possible
+        let scale = self.scale.min(ui.available_width() / PANEL_SIZE.x);
+        let pump_width = PANEL_SIZE.x * scale;
+        let columns = self.columns.unwrap_or_else(|| {
+            ((ui.available_width() + spacing.x) / (pump_width + spacing.x)).floor() as usize
+        });
+        let columns = columns.max(1);
+
+        let mut response: Option<Response> = None;
+        let mut clicked = None;
+        let mut dropped = None;
+
+        let grid = ui.push_id(self.id_salt, |ui| {
+            for (row, pumps) in self.pumps.chunks_mut(columns).enumerate() {
+                ui.horizontal(|ui| {
+                    for (column, pump) in pumps.iter_mut().enumerate() {
+                        let index = row * columns + column;
+                        let uid = pump.uid;
+                        let events_before = pump.events.len();
+                        let pump_response = ui.add(
+                            Pump::new(pump)
+                                .id_salt((self.id_salt, uid))
+                                .config(&self.config)
+                                .syringe_library(&self.syringe_library)
+                                .scale(scale)
+                                .draggable(self.reorderable),
+                        );
+                        if pump.events[events_before..].iter().any(|event| {
+                            matches!(
+                                event.kind,
+                                PumpEventKind::PanelClicked | PumpEventKind::PumpClicked
+                            )
+                        }) {
+                            clicked = Some(uid);
+                        }
+
+                        if self.reorderable {
+                            pump_response.dnd_set_drag_payload(uid);
+                            // Dropping on the right half of a pump puts the dragged pump after it
+                            let after = ui
+                                .input(|i| i.pointer.hover_pos())
+                                .is_some_and(|pos| pos.x > pump_response.rect.center().x);
+                            if let Some(dragged) = pump_response.dnd_hover_payload::<u32>() {
+                                if *dragged != uid {
+                                    drop_marker(ui, pump_response.rect, after);
+                                }
+                            }
+                            if let Some(dragged) = pump_response.dnd_release_payload::<u32>() {
+                                dropped = Some((*dragged, index + usize::from(after)));
+                            }
+                        }
+
+                        response = Some(match response.take() {
+                            Some(response) => response.union(pump_response),
+                            None => pump_response,
+                        });
+                    }
+                });
+            }
+        });
+
+        if let Some(uid) = clicked {
+            let modifiers = ui.input(|i| i.modifiers);
+            if modifiers.shift {
+                self.select_range(uid);
+            } else if modifiers.command {
+                self.toggle(uid);
+            } else {
+                self.select(uid);
+            }
+        }
+
+        // A dropped pump goes in front of or after the pump it was dropped on
+        let reordered =
+            match dropped.and_then(|(uid, index)| Some((uid, self.index_of(uid)?, index))) {
+                Some((uid, from, index)) if from + 1 != index && from != index => {
+                    self.move_to(uid, if from < index { index - 1 } else { index });
+                    true
+                }
+                _ => false,
+            };
+
+        PumpBankResponse {
+            response: response.unwrap_or(grid.response),
+            selection_changed: self.selected_uids() != selected_before,
+            reordered,
+        }
+    }
+}
+
+/// Insertion marker drawn at the left or right edge of the pump being dropped on
+fn drop_marker(ui: &Ui, rect: Rect, after: bool) {
+    let gap = ui.spacing().item_spacing.x / 2.0;
+    let x = if after {
+        rect.right() + gap
+    } else {
+        rect.left() - gap
+    };
+    ui.painter().vline(
+        x,
+        rect.y_range(),
+        Stroke::new(3.0, ui.visuals().selection.bg_fill),
+    );
+    ui.painter().rect_filled(
+        Rect::from_center_size(pos2(x, rect.top()), vec2(8.0, 8.0)),
+        2.0,
+        ui.visuals().selection.bg_fill,
+    );
+}
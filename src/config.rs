@@ -0,0 +1,413 @@
+use egui::{pos2, Color32, Pos2, Rect, Visuals};
+
+use crate::constants::{
+    FRAME_FILL_HOVER, FRAME_ROUNDING, FRAME_STROKE_COLOR_HOVER, FRAME_STROKE_WIDTH, LIQUID_COLOR,
+    MENU_BAR_HEIGHT,
+};
+use crate::icons::IconSet;
+use crate::run_state::{RunState, DEFAULT_LED_COLORS};
+use crate::DEFAULT_LINK_COLORS;
+
+/// Pump widget appearance
+///
+/// Colours left as ```None``` are taken from the current
+/// ```ui.style().visuals``` when the pump is drawn, so the
+/// default config follows the egui theme.
+///
+/// Sizes are given at a panel scale of 1.0.
+#[derive(Clone, Debug)]
+pub struct PumpConfig {
+    menu_bar_color: Option<Color32>,
+    menu_items_color: Option<Color32>,
+    frame_fill: Option<Color32>,
+    frame_fill_hover: Option<Color32>,
+    frame_stroke_color: Option<Color32>,
+    frame_stroke_color_hover: Option<Color32>,
+    selected_color: Option<Color32>,
+    frame_stroke_width: f32,
+    frame_rounding: f32,
+    text_color: Option<Color32>,
+    label_color: Option<Color32>,
+    arrow_color: Option<Color32>,
+    error_color: Option<Color32>,
+    liquid_color: Color32,
+    link_colors: [Color32; 6],
+    led_colors: [Color32; 6],
+    icons: IconSet,
+    layout: PumpLayout,
+    menu_bar_height: f32,
+    menu_font_size: f32,
+    pump_font_size: f32,
+    info_font_size: f32,
+}
+
+impl PumpConfig {
+    /// Config used by ```Pump::new``` unless another is attached
+    pub const DEFAULT: PumpConfig = PumpConfig {
+        menu_bar_color: None,
+        menu_items_color: None,
+        frame_fill: None,
+        frame_fill_hover: None,
+        frame_stroke_color: None,
+        frame_stroke_color_hover: None,
+        selected_color: None,
+        frame_stroke_width: FRAME_STROKE_WIDTH,
+        frame_rounding: FRAME_ROUNDING,
+        text_color: None,
+        label_color: None,
+        arrow_color: None,
+        error_color: None,
+        liquid_color: LIQUID_COLOR,
+        link_colors: DEFAULT_LINK_COLORS,
+        led_colors: DEFAULT_LED_COLORS,
+        icons: IconSet::DEFAULT,
+        layout: PumpLayout::DEFAULT,
+        menu_bar_height: MENU_BAR_HEIGHT,
+        menu_font_size: 12.0,
+        pump_font_size: 10.0,
+        info_font_size: 11.0,
+    };
+
+    /// Menu bar fill, defaults to the text colour
+    pub fn menu_bar_color(&self, visuals: &Visuals) -> Color32 {
+        self.menu_bar_color.unwrap_or(visuals.text_color())
+    }
+
+    /// Menu bar icons and text, defaults to the extreme background colour
+    pub fn menu_items_color(&self, visuals: &Visuals) -> Color32 {
+        self.menu_items_color.unwrap_or(visuals.extreme_bg_color)
+    }
+
+    /// Panel background, transparent by default
+    pub fn frame_fill(&self) -> Color32 {
+        self.frame_fill.unwrap_or(Color32::TRANSPARENT)
+    }
+
+    /// Panel background while hovered, ```FRAME_FILL_HOVER``` in dark mode
+    pub fn frame_fill_hover(&self, visuals: &Visuals) -> Color32 {
+        self.frame_fill_hover.unwrap_or(if visuals.dark_mode {
+            FRAME_FILL_HOVER
+        } else {
+            visuals.faint_bg_color
+        })
+    }
+
+    /// Panel border, defaults to the menu bar colour
+    pub fn frame_stroke_color(&self, visuals: &Visuals) -> Color32 {
+        self.frame_stroke_color
+            .unwrap_or_else(|| self.menu_bar_color(visuals))
+    }
+
+    /// Panel border while hovered, ```FRAME_STROKE_COLOR_HOVER``` in dark mode
+    pub fn frame_stroke_color_hover(&self, visuals: &Visuals) -> Color32 {
+        self.frame_stroke_color_hover
+            .unwrap_or(if visuals.dark_mode {
+                FRAME_STROKE_COLOR_HOVER
+            } else {
+                visuals.strong_text_color()
+            })
+    }
+
+    /// Highlight of a selected pump, defaults to the selection colour
+    pub fn selected_color(&self, visuals: &Visuals) -> Color32 {
+        self.selected_color.unwrap_or(visuals.selection.bg_fill)
+    }
+
+    pub fn frame_stroke_width(&self) -> f32 {
+        self.frame_stroke_width
+    }
+
+    pub fn frame_rounding(&self) -> f32 {
+        self.frame_rounding
+    }
+
+    /// Text on the pump body and info values
+    pub fn text_color(&self, visuals: &Visuals) -> Color32 {
+        self.text_color.unwrap_or(visuals.text_color())
+    }
+
+    /// Info face labels, defaults to the weak text colour
+    pub fn label_color(&self, visuals: &Visuals) -> Color32 {
+        self.label_color.unwrap_or(visuals.weak_text_color())
+    }
+
+    /// Dispense/withdraw arrows, defaults to the weak text colour
+    pub fn arrow_color(&self, visuals: &Visuals) -> Color32 {
+        self.arrow_color.unwrap_or(visuals.weak_text_color())
+    }
+
+    /// Error text, defaults to the error foreground colour
+    pub fn error_color(&self, visuals: &Visuals) -> Color32 {
+        self.error_color.unwrap_or(visuals.error_fg_color)
+    }
+
+    /// Liquid in the syringe barrel
+    pub fn liquid_color(&self) -> Color32 {
+        self.liquid_color
+    }
+
+    /// Accent colours for link groups A to F
+    pub fn link_colors(&self) -> &[Color32; 6] {
+        &self.link_colors
+    }
+
+    /// Status LED colours, one per ```RunState```
+    pub fn led_colors(&self) -> &[Color32; 6] {
+        &self.led_colors
+    }
+
+    /// Icons for each role, chosen per theme when drawn
+    pub fn icons(&self) -> &IconSet {
+        &self.icons
+    }
+
+    /// Where the icons, arrows and pump are drawn on the panel
+    pub fn layout(&self) -> &PumpLayout {
+        &self.layout
+    }
+
+    /// Menu bar height, the panel grows by any height above
+    /// ```MENU_BAR_HEIGHT``` so the bar never covers the pump
+    pub fn menu_bar_height(&self) -> f32 {
+        self.menu_bar_height
+    }
+
+    /// Height the menu bar adds above the default, which moves
+    /// everything below the bar down
+    pub fn menu_bar_extra(&self) -> f32 {
+        (self.menu_bar_height - MENU_BAR_HEIGHT).max(0.0)
+    }
+
+    pub fn menu_font_size(&self) -> f32 {
+        self.menu_font_size
+    }
+
+    pub fn pump_font_size(&self) -> f32 {
+        self.pump_font_size
+    }
+
+    pub fn info_font_size(&self) -> f32 {
+        self.info_font_size
+    }
+}
+
+/// Panel geometry
+///
+/// Positions are in points from the top left of the panel at a scale
+/// of 1.0. Menu bar items are laid out for a ```MENU_BAR_HEIGHT```
+/// bar and kept centred in taller ones; the arrows, pump and info rows
+/// move down by the extra bar height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PumpLayout {
+    /// Pump name in the menu bar
+    pub name: Pos2,
+    pub syringe_icon: Rect,
+    /// Syringe label next to the icon
+    pub syringe_text: Pos2,
+    pub flow_settings: Rect,
+    pub pump_settings: Rect,
+    pub link: Rect,
+    /// Link group label next to the link icon
+    pub link_text: Pos2,
+    /// Corner radius of the link group accent
+    pub link_rounding: f32,
+    pub speaker: Rect,
+    pub info: Rect,
+    pub led_center: Pos2,
+    pub led_radius: f32,
+    pub dispense_arrow: Rect,
+    pub withdraw_arrow: Rect,
+    pub pump: Rect,
+    /// Syringe barrel, relative to the top left of ```pump```
+    pub barrel: Rect,
+    /// Height of the pump name above the bottom of ```pump```
+    pub name_on_pump: f32,
+    /// First row of the info face
+    pub info_rows: Pos2,
+    /// Offset of the values from the labels on the info face
+    pub info_value_offset: f32,
+    pub info_row_height: f32,
+    /// How far the hover highlight reaches past an icon
+    pub hotspot_padding: f32,
+    pub hotspot_rounding: f32,
+}
+
+impl PumpLayout {
+    /// Layout of a 300 x 200 panel
+    pub const DEFAULT: PumpLayout = PumpLayout {
+        name: pos2(6.0, 5.0),
+        syringe_icon: Rect::from_min_max(pos2(80.0, 4.0), pos2(96.0, 20.0)),
+        syringe_text: pos2(100.0, 5.0),
+        flow_settings: Rect::from_min_max(pos2(148.0, 2.0), pos2(168.0, 22.0)),
+        pump_settings: Rect::from_min_max(pos2(174.0, 2.0), pos2(194.0, 22.0)),
+        link: Rect::from_min_max(pos2(200.0, 2.0), pos2(220.0, 22.0)),
+        link_text: pos2(220.0, 5.0),
+        link_rounding: 4.0,
+        speaker: Rect::from_min_max(pos2(240.0, 2.0), pos2(260.0, 22.0)),
+        info: Rect::from_min_max(pos2(270.0, 2.0), pos2(290.0, 22.0)),
+        led_center: pos2(265.0, 12.0),
+        led_radius: 4.0,
+        dispense_arrow: Rect::from_min_max(pos2(15.0, 60.0), pos2(45.0, 90.0)),
+        withdraw_arrow: Rect::from_min_max(pos2(255.0, 60.0), pos2(285.0, 90.0)),
+        pump: Rect::from_min_max(pos2(40.0, 40.0), pos2(240.0, 100.0)),
+        barrel: Rect::from_min_max(pos2(24.0, 6.0), pos2(150.0, 30.0)),
+        name_on_pump: 11.0,
+        info_rows: pos2(12.0, 34.0),
+        info_value_offset: 80.0,
+        info_row_height: 18.0,
+        hotspot_padding: 2.0,
+        hotspot_rounding: 3.0,
+    };
+}
+
+impl Default for PumpLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Default for PumpConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+// Builder starts here
+pub struct PumpConfigBuilder {
+    config: PumpConfig,
+}
+
+impl Default for PumpConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PumpConfigBuilder {
+    pub fn new() -> Self {
+        PumpConfigBuilder {
+            config: PumpConfig::DEFAULT,
+        }
+    }
+
+    pub fn menu_bar_color(mut self, color: Color32) -> Self {
+        self.config.menu_bar_color = Some(color);
+        self
+    }
+
+    pub fn menu_items_color(mut self, color: Color32) -> Self {
+        self.config.menu_items_color = Some(color);
+        self
+    }
+
+    pub fn frame_fill(mut self, color: Color32) -> Self {
+        self.config.frame_fill = Some(color);
+        self
+    }
+
+    pub fn frame_fill_hover(mut self, color: Color32) -> Self {
+        self.config.frame_fill_hover = Some(color);
+        self
+    }
+
+    pub fn frame_stroke_color(mut self, color: Color32) -> Self {
+        self.config.frame_stroke_color = Some(color);
+        self
+    }
+
+    pub fn frame_stroke_color_hover(mut self, color: Color32) -> Self {
+        self.config.frame_stroke_color_hover = Some(color);
+        self
+    }
+
+    pub fn selected_color(mut self, color: Color32) -> Self {
+        self.config.selected_color = Some(color);
+        self
+    }
+
+    pub fn frame_stroke_width(mut self, width: f32) -> Self {
+        self.config.frame_stroke_width = width;
+        self
+    }
+
+    pub fn frame_rounding(mut self, rounding: f32) -> Self {
+        self.config.frame_rounding = rounding;
+        self
+    }
+
+    pub fn text_color(mut self, color: Color32) -> Self {
+        self.config.text_color = Some(color);
+        self
+    }
+
+    pub fn label_color(mut self, color: Color32) -> Self {
+        self.config.label_color = Some(color);
+        self
+    }
+
+    pub fn arrow_color(mut self, color: Color32) -> Self {
+        self.config.arrow_color = Some(color);
+        self
+    }
+
+    pub fn error_color(mut self, color: Color32) -> Self {
+        self.config.error_color = Some(color);
+        self
+    }
+
+    pub fn liquid_color(mut self, color: Color32) -> Self {
+        self.config.liquid_color = color;
+        self
+    }
+
+    pub fn link_colors(mut self, link_colors: [Color32; 6]) -> Self {
+        self.config.link_colors = link_colors;
+        self
+    }
+
+    /// Status LED colours in ```RunState::ALL``` order
+    pub fn led_colors(mut self, led_colors: [Color32; 6]) -> Self {
+        self.config.led_colors = led_colors;
+        self
+    }
+
+    /// Status LED colour for one ```RunState```
+    pub fn led_color(mut self, state: RunState, color: Color32) -> Self {
+        self.config.led_colors[state as usize] = color;
+        self
+    }
+
+    pub fn icons(mut self, icons: IconSet) -> Self {
+        self.config.icons = icons;
+        self
+    }
+
+    pub fn layout(mut self, layout: PumpLayout) -> Self {
+        self.config.layout = layout;
+        self
+    }
+
+    pub fn menu_bar_height(mut self, height: f32) -> Self {
+        self.config.menu_bar_height = height;
+        self
+    }
+
+    pub fn menu_font_size(mut self, size: f32) -> Self {
+        self.config.menu_font_size = size;
+        self
+    }
+
+    pub fn pump_font_size(mut self, size: f32) -> Self {
+        self.config.pump_font_size = size;
+        self
+    }
+
+    pub fn info_font_size(mut self, size: f32) -> Self {
+        self.config.info_font_size = size;
+        self
+    }
+
+    pub fn build(self) -> PumpConfig {
+        self.config
+    }
+}
@@ -0,0 +1,305 @@
+use std::io::{Read, Write};
+
+use super::{
+    decimal, read_prompted_reply, Capabilities, DriverError, PumpDriver, PumpStatus, Totals,
+};
+use crate::{FlowDirection, FlowRate, RunState, Volume};
+
+/// Volume sent for runs without a target, more than any syringe holds
+const UNLIMITED_UL: f32 = 1e6;
+
+/// Driver for Chemyx Fusion series pumps
+///
+/// Speaks the Fusion serial command set: ```set units```, ```set
+/// diameter```, ```set rate```, ```set volume```, ```start```,
+/// ```stop```, ```pump status``` and ```dispensed volume```. Each reply
+/// is a line of data, if any, followed by the ```>``` prompt. Rates
+/// are sent in uL/min and volumes in uL.
+///
+/// The pump runs in the direction given by the sign of the volume,
+/// so the target volume is sent again with every run. The pump only
+/// reports the volume moved in the current run, so the totals are
+/// added up, and cleared, by the driver.
+///
+/// The Fusion 100 only infuses, the 4000 and 6000 have a pressure
+/// sensor, and all but the 100 run multi-step programs. Call
+/// ```discover``` to find out which model is connected.
+#[derive(Debug)]
+pub struct ChemyxDriver<T> {
+    transport: T,
+    capabilities: Capabilities,
+    units_sent: bool,
+    target: Option<Volume>,
+    direction: FlowDirection,
+    started: bool,
+    /// Volume of the current run already counted before the totals were cleared
+    run_offset: Volume,
+    finished: Totals,
+}
+
+impl<T: Read + Write> ChemyxDriver<T> {
+    pub fn new(transport: T) -> Self {
+        ChemyxDriver {
+            transport,
+            capabilities: Capabilities::BASIC,
+            units_sent: false,
+            target: None,
+            direction: FlowDirection::Dispense,
+            started: false,
+            run_offset: Volume::ZERO,
+            finished: Totals::default(),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Read the model with ```hardware version``` and look up what it
+    /// can do. Other models keep ```Capabilities::BASIC```.
+    pub fn discover(&mut self) -> Result<Capabilities, DriverError> {
+        let model = self.command("hardware version")?;
+        self.capabilities = fusion_capabilities(&model);
+        Ok(self.capabilities)
+    }
+
+    /// Send ```command``` and return the reply before the prompt.
+    pub fn command(&mut self, command: &str) -> Result<String, DriverError> {
+        self.transport
+            .write_all(format!("{command}\r").as_bytes())?;
+        self.transport.flush()?;
+
+        let reply = read_prompted_reply(&mut self.transport, |line| line == ">")?;
+        let data = reply
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && *line != ">")
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Refused commands are answered with e.g. "Invalid command"
+        let lower = data.to_ascii_lowercase();
+        if lower.contains("invalid") || lower.contains("error") || lower.contains("out of range") {
+            return Err(DriverError::Rejected(data));
+        }
+        Ok(data)
+    }
+
+    /// Switch the pump to uL/min and uL, once.
+    fn send_units(&mut self) -> Result<(), DriverError> {
+        if !self.units_sent {
+            self.command("set units 2")?;
+            self.units_sent = true;
+        }
+        Ok(())
+    }
+
+    /// Volume moved so far in the current or last run
+    fn run_volume(&mut self) -> Result<Volume, DriverError> {
+        let data = self.command("dispensed volume")?;
+        let value: f32 = data
+            .trim()
+            .parse()
+            .map_err(|_| DriverError::Protocol(data.clone()))?;
+        Ok(Volume::from_ul(value.abs()))
+    }
+
+    /// Totals of the finished runs plus the current one
+    fn run_totals(&mut self) -> Result<Totals, DriverError> {
+        let mut totals = self.finished;
+        if self.started {
+            let moved = self.run_volume()? - self.run_offset;
+            match self.direction {
+                FlowDirection::Dispense => totals.dispensed += moved,
+                FlowDirection::Withdraw => totals.withdrawn += moved,
+            }
+        }
+        Ok(totals)
+    }
+}
+
+/// Capabilities of the Fusion model named in ```model```
+fn fusion_capabilities(model: &str) -> Capabilities {
+    let number = model
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|part| !part.is_empty())
+        .unwrap_or_default();
+    match number {
+        "100" => Capabilities::INFUSE_ONLY,
+        "200" => Capabilities {
+            programs: true,
+            ..Capabilities::BASIC
+        },
+        "4000" | "6000" => Capabilities {
+            withdraw: true,
+            programs: true,
+            pressure: true,
+        },
+        _ => Capabilities::BASIC,
+    }
+}
+
+impl<T: Read + Write> PumpDriver for ChemyxDriver<T> {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        self.command(&format!("set diameter {}", decimal(diameter_mm)))
+            .map(drop)
+    }
+
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        self.send_units()?;
+        self.command(&format!("set rate {}", decimal(rate.as_ul_per_min())))
+            .map(drop)
+    }
+
+    /// Kept until the next run, which sends it with the direction.
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        self.target = volume;
+        Ok(())
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        if direction == FlowDirection::Withdraw && !self.capabilities.withdraw {
+            return Err(DriverError::Unsupported("withdraw"));
+        }
+        self.send_units()?;
+        self.finished = self.run_totals()?;
+        self.started = false;
+        self.run_offset = Volume::ZERO;
+
+        let volume = self.target.map_or(UNLIMITED_UL, |volume| volume.as_ul());
+        let sign = match direction {
+            FlowDirection::Dispense => "",
+            FlowDirection::Withdraw => "-",
+        };
+        self.command(&format!("set volume {sign}{}", decimal(volume)))?;
+        self.command("start")?;
+        self.direction = direction;
+        self.started = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.command("stop").map(drop)
+    }
+
+    /// ```pump status``` answers 0 stopped, 1 running, 2 paused,
+    /// 3 delayed or 4 stalled.
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        let data = self.command("pump status")?;
+        let (state, alarm) = match data.trim() {
+            "0" => (RunState::Idle, None),
+            "1" => (RunState::Running, None),
+            "2" | "3" => (RunState::Paused, None),
+            "4" => (RunState::Stalled, Some("Motor stalled".to_string())),
+            _ => return Err(DriverError::Protocol(data)),
+        };
+        Ok(PumpStatus {
+            state,
+            direction: self.direction,
+            alarm,
+        })
+    }
+
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        self.run_totals()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.run_offset = if self.started {
+            self.run_volume()?
+        } else {
+            Volume::ZERO
+        };
+        self.finished = Totals::default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScriptedTransport;
+
+    #[test]
+    fn capabilities_by_model() {
+        assert_eq!(fusion_capabilities("Fusion 100"), Capabilities::INFUSE_ONLY);
+        assert!(fusion_capabilities("Fusion 200 Touch").programs);
+        assert!(!fusion_capabilities("Fusion 200 Touch").pressure);
+        let high_pressure = fusion_capabilities("Fusion 6000-X v1.2");
+        assert!(high_pressure.withdraw && high_pressure.pressure);
+        assert_eq!(fusion_capabilities("Nanojet"), Capabilities::BASIC);
+    }
+
+    #[test]
+    fn discover() {
+        let mut driver = ChemyxDriver::new(ScriptedTransport::from_script([(
+            "hardware version\r",
+            "Fusion 100 v2.1\r\n>",
+        )]));
+        assert_eq!(driver.discover().unwrap(), Capabilities::INFUSE_ONLY);
+        assert_eq!(driver.capabilities(), Capabilities::INFUSE_ONLY);
+        assert!(matches!(
+            driver.run(FlowDirection::Withdraw),
+            Err(DriverError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn withdraw_sends_negative_volume() {
+        let mut driver = ChemyxDriver::new(ScriptedTransport::from_script([
+            ("set units 2\r", "\r\n>"),
+            ("set volume -250\r", "\r\n>"),
+            ("start\r", "\r\n>"),
+            ("pump status\r", "1\r\n>"),
+            ("dispensed volume\r", "100\r\n>"),
+            ("set volume 1000000\r", "\r\n>"),
+            ("start\r", "\r\n>"),
+        ]));
+        driver.set_volume(Some(Volume::from_ul(250.0))).unwrap();
+        driver.run(FlowDirection::Withdraw).unwrap();
+        let status = driver.status().unwrap();
+        assert_eq!(status.state, RunState::Running);
+        assert_eq!(status.direction, FlowDirection::Withdraw);
+
+        driver.set_volume(None).unwrap();
+        driver.run(FlowDirection::Dispense).unwrap();
+        assert!(driver.transport().is_done());
+        assert_eq!(driver.finished.withdrawn, Volume::from_ul(100.0));
+    }
+
+    #[test]
+    fn totals_and_clear() {
+        let mut driver = ChemyxDriver::new(ScriptedTransport::from_script([
+            ("set units 2\r", "\r\n>"),
+            ("set volume 1000000\r", "\r\n>"),
+            ("start\r", "\r\n>"),
+            ("dispensed volume\r", "40\r\n>"),
+            ("dispensed volume\r", "40\r\n>"),
+            ("dispensed volume\r", "55\r\n>"),
+        ]));
+        driver.run(FlowDirection::Dispense).unwrap();
+        assert_eq!(driver.totals().unwrap().dispensed, Volume::from_ul(40.0));
+        driver.clear_totals().unwrap();
+        assert_eq!(driver.totals().unwrap().dispensed, Volume::from_ul(15.0));
+    }
+
+    #[test]
+    fn rejected() {
+        let mut driver = ChemyxDriver::new(ScriptedTransport::from_script([(
+            "set diameter 0\r",
+            "Invalid value\r\n>",
+        )]));
+        assert!(matches!(
+            driver.set_diameter(0.0),
+            Err(DriverError::Rejected(_))
+        ));
+    }
+}
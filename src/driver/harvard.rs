@@ -0,0 +1,373 @@
+use std::io::{Read, Write};
+
+use super::{decimal, read_prompted_reply, DriverError, PumpDriver, PumpStatus, Totals};
+use crate::{FlowDirection, FlowRate, RunState, Volume};
+
+/// Driver for Harvard Apparatus Pump 11 Elite and PHD Ultra pumps
+///
+/// Speaks the Ultra command set: ```diameter```, ```irate```,
+/// ```wrate```, ```tvolume```, ```irun```, ```wrun```, ```stop```,
+/// ```ivolume``` and ```wvolume```. Each reply ends with a prompt
+/// showing what the pump is doing: ```:``` idle, ```>``` infusing,
+/// ```<``` withdrawing, ```*``` stalled and ```T*``` target volume
+/// reached, which is reported as ```RunState::EndOfStroke```. Pumps on
+/// a daisy chain put their address in front of the prompt, and
+/// commands to them start with the address.
+///
+/// While the pump is stalled, commands fail with
+/// ```DriverError::Alarm``` but status and volume queries still
+/// answer, so the stall reaches the widget through ```status```.
+#[derive(Debug)]
+pub struct HarvardDriver<T> {
+    transport: T,
+    address: Option<u8>,
+    state: RunState,
+    direction: FlowDirection,
+    target_reached: bool,
+}
+
+impl<T: Read + Write> HarvardDriver<T> {
+    pub fn new(transport: T) -> Self {
+        HarvardDriver {
+            transport,
+            address: None,
+            state: RunState::Idle,
+            direction: FlowDirection::Dispense,
+            target_reached: false,
+        }
+    }
+
+    /// Daisy chain address of the pump, 0 to 99
+    ///
+    /// # Panics
+    ///
+    /// If ```address``` is above 99.
+    pub fn address(mut self, address: u8) -> Self {
+        assert!(address <= 99, "pump address must be 0 to 99, got {address}");
+        self.address = Some(address);
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// The last prompt was ```T*```, the run stopped at its target volume
+    pub fn target_reached(&self) -> bool {
+        self.target_reached
+    }
+
+    /// Send ```command``` and return the reply lines before the prompt.
+    pub fn command(&mut self, command: &str) -> Result<Vec<String>, DriverError> {
+        let lines = self.query(command)?;
+        if self.state == RunState::Stalled {
+            return Err(DriverError::Alarm("Motor stalled".to_string()));
+        }
+        Ok(lines)
+    }
+
+    /// Like ```command```, but a stall prompt is not an error.
+    fn query(&mut self, command: &str) -> Result<Vec<String>, DriverError> {
+        let request = match self.address {
+            Some(address) => format!("{address:02}{command}\r"),
+            None => format!("{command}\r"),
+        };
+        self.transport.write_all(request.as_bytes())?;
+        self.transport.flush()?;
+
+        let reply = read_prompted_reply(&mut self.transport, is_prompt)?;
+        let mut lines: Vec<String> = reply
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        let prompt = lines
+            .pop()
+            .ok_or_else(|| DriverError::Protocol(reply.clone()))?;
+        self.parse_prompt(&prompt)
+            .ok_or_else(|| DriverError::Protocol(reply.clone()))?;
+
+        // Refused commands are answered with e.g. "Argument error" or "Out of range"
+        if let Some(error) = lines.iter().find(|line| {
+            let line = line.to_ascii_lowercase();
+            line.contains("error") || line.contains("out of range")
+        }) {
+            return Err(DriverError::Rejected(error.clone()));
+        }
+        Ok(lines)
+    }
+
+    /// Set only the infuse rate, for infuse-only pumps.
+    pub(crate) fn set_infuse_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        self.command(&format!("irate {}", rate_argument(rate)))
+            .map(drop)
+    }
+
+    /// Volume infused since the totals were last cleared
+    pub(crate) fn infused(&mut self) -> Result<Volume, DriverError> {
+        self.volume("ivolume")
+    }
+
+    /// Send a volume query such as ```ivolume``` and parse the reply.
+    fn volume(&mut self, command: &str) -> Result<Volume, DriverError> {
+        let lines = self.query(command)?;
+        let line = lines.first().map(String::as_str).unwrap_or_default();
+        parse_volume(line).ok_or_else(|| DriverError::Protocol(line.to_string()))
+    }
+
+    /// Track the pump state from a prompt such as ```01>```.
+    fn parse_prompt(&mut self, prompt: &str) -> Option<()> {
+        if !is_prompt(prompt) {
+            return None;
+        }
+        let digits = prompt.trim_start_matches(|c: char| c.is_ascii_digit());
+        let address = &prompt[..prompt.len() - digits.len()];
+        if let Some(expected) = self.address {
+            if address.parse::<u8>().ok()? != expected {
+                return None;
+            }
+        }
+        self.target_reached = digits == "T*";
+        self.state = match digits {
+            ">" => {
+                self.direction = FlowDirection::Dispense;
+                RunState::Running
+            }
+            "<" => {
+                self.direction = FlowDirection::Withdraw;
+                RunState::Running
+            }
+            "*" => RunState::Stalled,
+            "T*" => RunState::EndOfStroke,
+            _ => RunState::Idle,
+        };
+        Some(())
+    }
+}
+
+/// An optional address, then ```:```, ```>```, ```<```, ```*``` or ```T*```
+fn is_prompt(line: &str) -> bool {
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    matches!(rest, ":" | ">" | "<" | "*" | "T*")
+}
+
+/// A rate and its units, in nL/min, uL/min or mL/min by size
+fn rate_argument(rate: FlowRate) -> String {
+    let (value, units) = if rate.as_ul_per_min() < 1.0 {
+        (rate.as_nl_per_min(), "nl/min")
+    } else if rate.as_ul_per_min() < 1e4 {
+        (rate.as_ul_per_min(), "ul/min")
+    } else {
+        (rate.as_ml_per_min(), "ml/min")
+    };
+    format!("{} {units}", decimal(value))
+}
+
+/// Parse a volume reply such as "1.234 ml" or "250 ul"
+fn parse_volume(line: &str) -> Option<Volume> {
+    let (value, units) = line.split_once(' ')?;
+    let value: f32 = value.parse().ok()?;
+    match units.trim() {
+        "pl" => Some(Volume::from_nl(value / 1e3)),
+        "nl" => Some(Volume::from_nl(value)),
+        "ul" => Some(Volume::from_ul(value)),
+        "ml" => Some(Volume::from_ml(value)),
+        "l" => Some(Volume::from_ml(value * 1e3)),
+        _ => None,
+    }
+}
+
+impl<T: Read + Write> PumpDriver for HarvardDriver<T> {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        self.command(&format!("diameter {}", decimal(diameter_mm)))
+            .map(drop)
+    }
+
+    /// Sets both the infuse and withdraw rates.
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        let rate = rate_argument(rate);
+        self.command(&format!("irate {rate}"))?;
+        self.command(&format!("wrate {rate}")).map(drop)
+    }
+
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        match volume {
+            Some(volume) if volume.as_ul() < 1e4 => {
+                self.command(&format!("tvolume {} ul", decimal(volume.as_ul())))
+            }
+            Some(volume) => self.command(&format!("tvolume {} ml", decimal(volume.as_ml()))),
+            None => self.command("ctvolume"),
+        }
+        .map(drop)
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        self.command(match direction {
+            FlowDirection::Dispense => "irun",
+            FlowDirection::Withdraw => "wrun",
+        })
+        .map(drop)
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.command("stop").map(drop)
+    }
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        // An empty command is answered with just the prompt
+        self.query("")?;
+        Ok(PumpStatus {
+            state: self.state,
+            direction: self.direction,
+            alarm: (self.state == RunState::Stalled).then(|| "Motor stalled".to_string()),
+        })
+    }
+
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        Ok(Totals {
+            dispensed: self.infused()?,
+            withdrawn: self.volume("wvolume")?,
+        })
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.command("cvolume").map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use web_time::Instant;
+
+    use super::*;
+    use crate::{DriverLink, ScriptedTransport};
+
+    #[test]
+    fn prompts() {
+        let mut driver = HarvardDriver::new(ScriptedTransport::from_script([
+            ("\r", "\r\n:"),
+            ("\r", "\r\n>"),
+            ("\r", "\r\n<"),
+            ("\r", "\r\nT*"),
+            ("\r", "\r\n*"),
+        ]));
+        let mut status = || driver.status().unwrap();
+        assert_eq!(status().state, RunState::Idle);
+        let infusing = status();
+        assert_eq!(infusing.state, RunState::Running);
+        assert_eq!(infusing.direction, FlowDirection::Dispense);
+        let withdrawing = status();
+        assert_eq!(withdrawing.state, RunState::Running);
+        assert_eq!(withdrawing.direction, FlowDirection::Withdraw);
+        assert_eq!(status().state, RunState::EndOfStroke);
+        let stalled = status();
+        assert_eq!(stalled.state, RunState::Stalled);
+        assert_eq!(stalled.alarm.as_deref(), Some("Motor stalled"));
+        assert!(!driver.target_reached());
+    }
+
+    #[test]
+    fn target_reached() {
+        let mut driver = HarvardDriver::new(ScriptedTransport::from_script([
+            ("irun\r", "\r\n>"),
+            ("\r", "\r\nT*"),
+        ]));
+        driver.run(FlowDirection::Dispense).unwrap();
+        assert!(!driver.target_reached());
+        assert_eq!(driver.status().unwrap().state, RunState::EndOfStroke);
+        assert!(driver.target_reached());
+    }
+
+    #[test]
+    fn stall_reaches_link() {
+        let driver = HarvardDriver::new(ScriptedTransport::from_script([
+            ("\r", "\r\n*"),
+            ("ivolume\r", "\r\n0.25 ml\r\n*"),
+            ("wvolume\r", "\r\n0 ml\r\n*"),
+            ("irun\r", "\r\n*"),
+        ]));
+        let mut link = DriverLink::new(driver);
+        let (status, totals) = link.poll(Instant::now()).unwrap().unwrap();
+        assert_eq!(status.state, RunState::Stalled);
+        assert_eq!(totals.dispensed, Volume::from_ml(0.25));
+
+        // Commands are refused until the stall is cleared
+        assert!(matches!(
+            link.driver_mut().run(FlowDirection::Dispense),
+            Err(DriverError::Alarm(_))
+        ));
+    }
+
+    #[test]
+    fn addressed_prompts() {
+        let transport = ScriptedTransport::from_script([
+            ("07irun\r", "\r\n07>"),
+            ("07\r", "\r\n07T*"),
+            ("07stop\r", "\r\n08:"),
+        ]);
+        let mut driver = HarvardDriver::new(transport).address(7);
+        driver.run(FlowDirection::Dispense).unwrap();
+        assert_eq!(driver.status().unwrap().state, RunState::EndOfStroke);
+        assert!(matches!(driver.stop(), Err(DriverError::Protocol(_))));
+    }
+
+    #[test]
+    #[should_panic(expected = "0 to 99")]
+    fn address_out_of_range() {
+        let _ = HarvardDriver::new(ScriptedTransport::new()).address(100);
+    }
+
+    #[test]
+    fn rejected() {
+        let mut driver = HarvardDriver::new(ScriptedTransport::from_script([(
+            "diameter 500\r",
+            "\r\nArgument error\r\n:",
+        )]));
+        match driver.set_diameter(500.0) {
+            Err(DriverError::Rejected(reason)) => assert_eq!(reason, "Argument error"),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn arguments() {
+        let mut driver = HarvardDriver::new(ScriptedTransport::from_script([
+            ("diameter 14.57\r", "\r\n:"),
+            ("irate 500 nl/min\r", "\r\n:"),
+            ("wrate 500 nl/min\r", "\r\n:"),
+            ("irate 12.5 ul/min\r", "\r\n:"),
+            ("wrate 12.5 ul/min\r", "\r\n:"),
+            ("irate 20 ml/min\r", "\r\n:"),
+            ("wrate 20 ml/min\r", "\r\n:"),
+            ("tvolume 250 ul\r", "\r\n:"),
+            ("tvolume 15 ml\r", "\r\n:"),
+            ("ctvolume\r", "\r\n:"),
+            ("wrun\r", "\r\n<"),
+            ("stop\r", "\r\n:"),
+        ]));
+        driver.set_diameter(14.57).unwrap();
+        driver.set_rate(FlowRate::from_nl_per_min(500.0)).unwrap();
+        driver.set_rate(FlowRate::from_ul_per_min(12.5)).unwrap();
+        driver.set_rate(FlowRate::from_ml_per_min(20.0)).unwrap();
+        driver.set_volume(Some(Volume::from_ul(250.0))).unwrap();
+        driver.set_volume(Some(Volume::from_ml(15.0))).unwrap();
+        driver.set_volume(None).unwrap();
+        driver.run(FlowDirection::Withdraw).unwrap();
+        driver.stop().unwrap();
+        assert!(driver.transport().is_done());
+    }
+
+    #[test]
+    fn volumes() {
+        assert_eq!(parse_volume("1.234 ml"), Some(Volume::from_ml(1.234)));
+        assert_eq!(parse_volume("250 ul"), Some(Volume::from_ul(250.0)));
+        assert_eq!(parse_volume("40 nl"), Some(Volume::from_nl(40.0)));
+        assert_eq!(parse_volume("12 gal"), None);
+        assert_eq!(parse_volume("ul"), None);
+    }
+}
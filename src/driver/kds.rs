@@ -0,0 +1,185 @@
+use std::io::{Read, Write};
+
+use super::{Capabilities, DriverError, HarvardDriver, PumpDriver, PumpStatus, Totals};
+use crate::{FlowDirection, FlowRate, Volume};
+
+/// Driver for KD Scientific Legato series pumps
+///
+/// The Legato speaks the same text protocol as the Harvard Ultra
+/// command set, so commands go through a ```HarvardDriver```. Pumps
+/// on a daisy chain are addressed the same way, see
+/// ```HarvardDriver::address```.
+///
+/// The Legato 100, 101, 200 and 201 only infuse, the other models
+/// also withdraw. The 180 and the 200 series run multi-step programs.
+/// Call ```discover``` to find out which model is connected.
+#[derive(Debug)]
+pub struct LegatoDriver<T> {
+    inner: HarvardDriver<T>,
+    capabilities: Capabilities,
+}
+
+impl<T: Read + Write> LegatoDriver<T> {
+    pub fn new(transport: T) -> Self {
+        LegatoDriver {
+            inner: HarvardDriver::new(transport),
+            capabilities: Capabilities::BASIC,
+        }
+    }
+
+    /// See ```HarvardDriver::address```
+    pub fn address(mut self, address: u8) -> Self {
+        self.inner = self.inner.address(address);
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        self.inner.transport()
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        self.inner.transport_mut()
+    }
+
+    /// See ```HarvardDriver::target_reached```
+    pub fn target_reached(&self) -> bool {
+        self.inner.target_reached()
+    }
+
+    /// Send a raw command, see ```HarvardDriver::command```
+    pub fn command(&mut self, command: &str) -> Result<Vec<String>, DriverError> {
+        self.inner.command(command)
+    }
+
+    /// Query ```version``` and set the capabilities of the reported
+    /// Legato model, or ```Capabilities::BASIC``` if none is named.
+    pub fn discover(&mut self) -> Result<Capabilities, DriverError> {
+        let lines = self.inner.command("version")?;
+        self.capabilities = lines
+            .iter()
+            .find_map(|line| legato_capabilities(line))
+            .unwrap_or(Capabilities::BASIC);
+        Ok(self.capabilities)
+    }
+}
+
+/// Capabilities of the Legato model named in ```line```, e.g. "Legato 210 1.05"
+fn legato_capabilities(line: &str) -> Option<Capabilities> {
+    let lower = line.to_ascii_lowercase();
+    let model = lower.split_once("legato")?.1.split_whitespace().next()?;
+    Some(match model {
+        "100" | "101" => Capabilities::INFUSE_ONLY,
+        "200" | "201" => Capabilities {
+            programs: true,
+            ..Capabilities::INFUSE_ONLY
+        },
+        "180" | "210" | "270" => Capabilities {
+            programs: true,
+            ..Capabilities::BASIC
+        },
+        _ => Capabilities::BASIC,
+    })
+}
+
+impl<T: Read + Write> PumpDriver for LegatoDriver<T> {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        self.inner.set_diameter(diameter_mm)
+    }
+
+    /// Infuse-only models are only sent the infuse rate.
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        if self.capabilities.withdraw {
+            self.inner.set_rate(rate)
+        } else {
+            self.inner.set_infuse_rate(rate)
+        }
+    }
+
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        self.inner.set_volume(volume)
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        if direction == FlowDirection::Withdraw && !self.capabilities.withdraw {
+            return Err(DriverError::Unsupported("withdraw"));
+        }
+        self.inner.run(direction)
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.inner.stop()
+    }
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        self.inner.status()
+    }
+
+    /// Infuse-only models have no withdrawn total.
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        if self.capabilities.withdraw {
+            self.inner.totals()
+        } else {
+            Ok(Totals {
+                dispensed: self.inner.infused()?,
+                withdrawn: Volume::ZERO,
+            })
+        }
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.inner.clear_totals()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScriptedTransport;
+
+    #[test]
+    fn capabilities_by_model() {
+        assert_eq!(
+            legato_capabilities("LEGATO 100"),
+            Some(Capabilities::INFUSE_ONLY)
+        );
+        assert_eq!(
+            legato_capabilities("Legato 110 1.05"),
+            Some(Capabilities::BASIC)
+        );
+        assert_eq!(legato_capabilities("Legato 111"), Some(Capabilities::BASIC));
+        let infuse_programs = legato_capabilities("Legato 201").unwrap();
+        assert!(infuse_programs.programs && !infuse_programs.withdraw);
+        for model in ["Legato 180", "Legato 210", "Legato 270 2.0"] {
+            let capabilities = legato_capabilities(model).unwrap();
+            assert!(capabilities.programs && capabilities.withdraw, "{model}");
+        }
+        assert_eq!(legato_capabilities("Firmware 1.05"), None);
+    }
+
+    #[test]
+    fn infuse_only() {
+        let transport = ScriptedTransport::new()
+            .expect("version\r", "\r\nLegato 100 1.05\r\n:")
+            .expect("irate 5 ul/min\r", "\r\n:")
+            .expect("ivolume\r", "\r\n2 ul\r\n:");
+        let mut driver = LegatoDriver::new(transport);
+        assert_eq!(driver.discover().unwrap(), Capabilities::INFUSE_ONLY);
+        driver.set_rate(FlowRate::from_ul_per_min(5.0)).unwrap();
+        assert!(matches!(
+            driver.run(FlowDirection::Withdraw),
+            Err(DriverError::Unsupported(_))
+        ));
+        assert_eq!(
+            driver.totals().unwrap(),
+            Totals {
+                dispensed: Volume::from_ul(2.0),
+                withdrawn: Volume::ZERO,
+            }
+        );
+        assert!(driver.transport().is_done());
+    }
+}
@@ -0,0 +1,414 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+use super::{Capabilities, DriverError, PumpDriver, PumpStatus, Totals};
+use crate::{FlowDirection, FlowRate, PumpSimulator, RunState, SyringeSpec, Volume};
+
+/// A command received by a ```MockDriver```
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCommand {
+    SetDiameter(f32),
+    SetRate(FlowRate),
+    SetVolume(Option<Volume>),
+    Run(FlowDirection),
+    Stop,
+    Status,
+    Totals,
+    ClearTotals,
+}
+
+/// An in-memory pump for tests and demos
+///
+/// Runs a ```PumpSimulator``` and records every command it receives.
+/// The simulator follows the wall clock unless ```manual_clock``` is
+/// set, in which case time only passes through ```advance```.
+/// Failures can be injected with ```fail_next```.
+#[derive(Debug)]
+pub struct MockDriver {
+    simulator: PumpSimulator,
+    commands: Vec<MockCommand>,
+    fail_next: Option<DriverError>,
+    alarm: Option<String>,
+    last_tick: Option<Instant>,
+    manual_clock: bool,
+    capabilities: Capabilities,
+}
+
+impl MockDriver {
+    pub fn new(spec: SyringeSpec) -> Self {
+        MockDriver {
+            simulator: PumpSimulator::new(spec),
+            commands: Vec::new(),
+            fail_next: None,
+            alarm: None,
+            last_tick: None,
+            manual_clock: false,
+            capabilities: Capabilities::BASIC,
+        }
+    }
+
+    /// Only let time pass through ```advance```.
+    pub fn manual_clock(mut self) -> Self {
+        self.manual_clock = true;
+        self
+    }
+
+    /// Pretend to be a pump with ```capabilities```, e.g. an infuse-only one.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        self.simulator.advance(dt);
+    }
+
+    /// Fail the next command with ```error```.
+    pub fn fail_next(&mut self, error: DriverError) {
+        self.fail_next = Some(error);
+    }
+
+    /// Stall the pump and report ```alarm``` until it is run again.
+    pub fn raise_alarm(&mut self, alarm: impl Into<String>) {
+        self.simulator.stop();
+        self.alarm = Some(alarm.into());
+    }
+
+    /// Commands received so far, oldest first
+    pub fn commands(&self) -> &[MockCommand] {
+        &self.commands
+    }
+
+    pub fn clear_commands(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn simulator(&self) -> &PumpSimulator {
+        &self.simulator
+    }
+
+    pub fn simulator_mut(&mut self) -> &mut PumpSimulator {
+        &mut self.simulator
+    }
+
+    /// Record ```command```, fail it if asked to, and catch up with the clock
+    fn receive(&mut self, command: MockCommand) -> Result<(), DriverError> {
+        self.commands.push(command);
+        if !self.manual_clock {
+            let now = Instant::now();
+            if let Some(last) = self.last_tick {
+                self.simulator.advance(now - last);
+            }
+            self.last_tick = Some(now);
+        }
+        match self.fail_next.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl PumpDriver for MockDriver {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        self.receive(MockCommand::SetDiameter(diameter_mm))?;
+        let spec = SyringeSpec {
+            inner_diameter_mm: diameter_mm,
+            ..self.simulator.spec().clone()
+        };
+        self.simulator.set_spec(spec);
+        Ok(())
+    }
+
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        self.receive(MockCommand::SetRate(rate))?;
+        self.simulator.set_flow_rate(rate);
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        self.receive(MockCommand::SetVolume(volume))?;
+        self.simulator.set_target(volume);
+        Ok(())
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        self.receive(MockCommand::Run(direction))?;
+        if direction == FlowDirection::Withdraw && !self.capabilities.withdraw {
+            return Err(DriverError::Unsupported("withdraw"));
+        }
+        self.alarm = None;
+        self.simulator.set_direction(direction);
+        self.simulator.start();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.receive(MockCommand::Stop)?;
+        self.simulator.stop();
+        Ok(())
+    }
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        self.receive(MockCommand::Status)?;
+        let state = match self.alarm {
+            Some(_) => RunState::Stalled,
+            None => self.simulator.run_state(),
+        };
+        Ok(PumpStatus {
+            state,
+            direction: self.simulator.direction(),
+            alarm: self.alarm.clone(),
+        })
+    }
+
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        self.receive(MockCommand::Totals)?;
+        Ok(Totals {
+            dispensed: self.simulator.dispensed(),
+            withdrawn: self.simulator.withdrawn(),
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.receive(MockCommand::ClearTotals)?;
+        self.simulator.reset_totals();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        builtin_syringe, DriverLink, PumpButton, PumpData, PumpEvent, PumpEventKind, Syringeset,
+    };
+
+    /// A pump bound to a shared mock driver that polls on every sync
+    fn pump() -> (PumpData, Rc<RefCell<MockDriver>>) {
+        let spec = builtin_syringe("Hamilton", "1001").unwrap().clone();
+        let mut mock = MockDriver::new(spec).manual_clock();
+        mock.simulator_mut().fill(Volume::from_ul(500.0));
+        let mock = Rc::new(RefCell::new(mock));
+        let pump = PumpData {
+            syringeset: Syringeset::UL1000,
+            flow_rate: FlowRate::from_ul_per_min(100.0),
+            volume: Volume::from_ul(500.0),
+            driver: Some(DriverLink::new(Rc::clone(&mock)).poll_interval(Duration::ZERO)),
+            ..Default::default()
+        };
+        (pump, mock)
+    }
+
+    fn press(pump: &mut PumpData, time: f64, kind: PumpEventKind) {
+        pump.events.push(PumpEvent {
+            uid: pump.uid,
+            time,
+            kind,
+        });
+        pump.sync_driver();
+    }
+
+    fn runs(mock: &Rc<RefCell<MockDriver>>) -> usize {
+        let mock = mock.borrow();
+        let runs = mock.commands().iter();
+        runs.filter(|c| matches!(c, MockCommand::Run(_))).count()
+    }
+
+    #[test]
+    fn click_starts_and_stops() {
+        let (mut pump, mock) = pump();
+        press(
+            &mut pump,
+            1.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        assert_eq!(pump.run_state.state(), RunState::Running);
+        let diameter = builtin_syringe("Hamilton", "1001")
+            .unwrap()
+            .inner_diameter_mm;
+        assert!(mock
+            .borrow()
+            .commands()
+            .contains(&MockCommand::SetDiameter(diameter)));
+        assert!(mock
+            .borrow()
+            .commands()
+            .contains(&MockCommand::Run(FlowDirection::Dispense)));
+
+        press(
+            &mut pump,
+            2.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        assert_eq!(pump.run_state.state(), RunState::Idle);
+        assert!(mock.borrow().commands().contains(&MockCommand::Stop));
+        assert_eq!(pump.last_error, None);
+    }
+
+    #[test]
+    fn hold_runs_once_until_release() {
+        let (mut pump, mock) = pump();
+        let held = |held_for| PumpEventKind::ButtonHeld {
+            button: PumpButton::Withdraw,
+            held_for,
+        };
+        press(&mut pump, 1.0, held(0.4));
+        assert_eq!(pump.run_state.state(), RunState::Running);
+        assert_eq!(pump.direction, FlowDirection::Withdraw);
+
+        // The pump stops by itself while the button is still held
+        mock.borrow_mut().simulator_mut().stop();
+        press(&mut pump, 1.1, held(0.5));
+        press(&mut pump, 1.2, held(0.6));
+        assert_eq!(runs(&mock), 1);
+        assert_eq!(pump.run_state.state(), RunState::Idle);
+
+        press(
+            &mut pump,
+            1.3,
+            PumpEventKind::ButtonReleased {
+                button: PumpButton::Withdraw,
+                held_for: 0.7,
+            },
+        );
+        assert!(mock.borrow().commands().contains(&MockCommand::Stop));
+
+        // A new press runs again
+        press(&mut pump, 2.0, held(0.4));
+        assert_eq!(runs(&mock), 2);
+    }
+
+    #[test]
+    fn failed_command_is_last_error() {
+        let (mut pump, mock) = pump();
+        mock.borrow_mut().fail_next(DriverError::Timeout);
+        press(
+            &mut pump,
+            1.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        assert_eq!(pump.last_error.as_deref(), Some("pump did not respond"));
+    }
+
+    #[test]
+    fn alarm_stalls_pump() {
+        let (mut pump, mock) = pump();
+        press(
+            &mut pump,
+            1.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        mock.borrow_mut().raise_alarm("Motor stalled");
+        pump.sync_driver();
+        assert_eq!(pump.run_state.state(), RunState::Stalled);
+        assert_eq!(pump.last_error.as_deref(), Some("Motor stalled"));
+    }
+
+    #[test]
+    fn release_stops_after_rate_change_and_failure() {
+        let (mut pump, mock) = pump();
+        let held = PumpEventKind::ButtonHeld {
+            button: PumpButton::Dispense,
+            held_for: 0.4,
+        };
+        press(&mut pump, 1.0, held);
+        assert!(mock.borrow().simulator().is_running());
+
+        // A frame with a new rate whose poll fails
+        pump.flow_rate = FlowRate::from_ul_per_min(200.0);
+        mock.borrow_mut().fail_next(DriverError::Timeout);
+        pump.sync_driver();
+        assert_eq!(pump.last_error.as_deref(), Some("pump did not respond"));
+
+        press(
+            &mut pump,
+            1.5,
+            PumpEventKind::ButtonReleased {
+                button: PumpButton::Dispense,
+                held_for: 0.9,
+            },
+        );
+        assert!(mock.borrow().commands().contains(&MockCommand::Stop));
+        assert!(!mock.borrow().simulator().is_running());
+        assert_eq!(pump.run_state.state(), RunState::Idle);
+        // The new rate waits for the next run
+        let new_rate = MockCommand::SetRate(FlowRate::from_ul_per_min(200.0));
+        assert!(!mock.borrow().commands().contains(&new_rate));
+
+        press(
+            &mut pump,
+            2.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        let commands = mock.borrow().commands().to_vec();
+        let rate_at = commands.iter().position(|c| *c == new_rate).unwrap();
+        assert_eq!(
+            commands[rate_at + 1],
+            MockCommand::Run(FlowDirection::Dispense)
+        );
+    }
+
+    #[test]
+    fn failed_run_waits_to_retry() {
+        let (mut pump, mock) = pump();
+        pump.driver = pump
+            .driver
+            .take()
+            .map(|link| link.poll_interval(Duration::from_secs(60)));
+        mock.borrow_mut().fail_next(DriverError::Timeout);
+        let click = PumpEventKind::ButtonClicked(PumpButton::Dispense);
+        press(&mut pump, 1.0, click.clone());
+        press(&mut pump, 2.0, click);
+        // The first press failed on the rate, the second waits
+        assert_eq!(runs(&mock), 0);
+        assert_eq!(pump.driver.as_ref().unwrap().handled_until(), 1.0);
+
+        // A stop is sent at once and the press before it is dropped
+        press(
+            &mut pump,
+            3.0,
+            PumpEventKind::ButtonReleased {
+                button: PumpButton::Dispense,
+                held_for: 0.9,
+            },
+        );
+        assert_eq!(runs(&mock), 0);
+        assert_eq!(pump.driver.as_ref().unwrap().handled_until(), 3.0);
+        assert!(mock.borrow().commands().contains(&MockCommand::Stop));
+    }
+
+    #[test]
+    fn volume_follows_totals() {
+        let (mut pump, mock) = pump();
+        press(
+            &mut pump,
+            1.0,
+            PumpEventKind::ButtonClicked(PumpButton::Dispense),
+        );
+        mock.borrow_mut().advance(Duration::from_secs(60));
+        pump.sync_driver();
+        assert!((pump.volume.as_ul() - 400.0).abs() < 0.1);
+
+        press(
+            &mut pump,
+            2.0,
+            PumpEventKind::ButtonClicked(PumpButton::Withdraw),
+        );
+        mock.borrow_mut().advance(Duration::from_secs(30));
+        pump.sync_driver();
+        assert!((pump.volume.as_ul() - 450.0).abs() < 0.1);
+
+        // Clearing the totals leaves the volume alone
+        mock.borrow_mut().clear_totals().unwrap();
+        pump.sync_driver();
+        assert!((pump.volume.as_ul() - 450.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,399 @@
+//! Talking to real syringe pumps
+//!
+//! A ```PumpDriver``` sends commands to one pump. Bind it to a
+//! ```PumpData``` through a ```DriverLink``` and call
+//! ```PumpData::sync_driver``` once per frame, after the pump
+//! widget has been shown, to send its button presses to the pump
+//! and read the pump status back.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::{FlowDirection, FlowRate, PumpButton, RunState, Volume};
+
+pub mod chemyx;
+pub mod harvard;
+pub mod kds;
+pub mod mock;
+pub mod newera;
+pub mod transport;
+
+pub use chemyx::ChemyxDriver;
+pub use harvard::HarvardDriver;
+pub use kds::LegatoDriver;
+pub use mock::{MockCommand, MockDriver};
+pub use newera::NewEraDriver;
+pub use transport::{ScriptedTransport, SharedTransport};
+
+/// Why a pump command failed
+#[derive(Debug)]
+pub enum DriverError {
+    /// The transport failed, e.g. the serial port was unplugged
+    Io(io::Error),
+    /// The pump did not answer in time
+    Timeout,
+    /// The pump answered with something that could not be understood
+    Protocol(String),
+    /// The pump understood the command but refused it
+    Rejected(String),
+    /// The pump answered with an alarm instead of carrying out the command
+    Alarm(String),
+    /// The pump has no such feature
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::Io(error) => write!(f, "I/O error: {error}"),
+            DriverError::Timeout => write!(f, "pump did not respond"),
+            DriverError::Protocol(reply) => write!(f, "unexpected reply: {reply}"),
+            DriverError::Rejected(reason) => write!(f, "command rejected: {reason}"),
+            DriverError::Alarm(alarm) => write!(f, "pump alarm: {alarm}"),
+            DriverError::Unsupported(feature) => write!(f, "{feature} not supported"),
+        }
+    }
+}
+
+impl std::error::Error for DriverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DriverError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DriverError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => DriverError::Timeout,
+            _ => DriverError::Io(error),
+        }
+    }
+}
+
+/// Longest text reply accepted before giving up on finding its prompt
+const MAX_REPLY_LEN: usize = 256;
+
+/// Read a text reply up to and including its prompt, for line-based
+/// protocols that end every reply with a prompt line.
+///
+/// ```is_prompt``` is asked about the trimmed current line after each
+/// byte, and reading stops when it says yes.
+pub(crate) fn read_prompted_reply(
+    transport: &mut impl Read,
+    is_prompt: impl Fn(&str) -> bool,
+) -> Result<String, DriverError> {
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    loop {
+        if transport.read(&mut byte)? == 0 {
+            return Err(DriverError::Timeout);
+        }
+        reply.push(byte[0]);
+        let line_start = reply.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        if is_prompt(String::from_utf8_lossy(&reply[line_start..]).trim()) {
+            return Ok(String::from_utf8_lossy(&reply).into_owned());
+        }
+        if reply.len() > MAX_REPLY_LEN {
+            return Err(DriverError::Protocol(
+                String::from_utf8_lossy(&reply).into_owned(),
+            ));
+        }
+    }
+}
+
+/// Format a number argument with at most four decimals and no trailing zeros
+pub(crate) fn decimal(value: f32) -> String {
+    let text = format!("{value:.4}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// What the pump reported when asked for its status
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpStatus {
+    pub state: RunState,
+    pub direction: FlowDirection,
+    /// Alarm text shown as the pump's last error, e.g. "Motor stalled"
+    pub alarm: Option<String>,
+}
+
+/// Volumes moved since the totals were last cleared
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Totals {
+    pub dispensed: Volume,
+    pub withdrawn: Volume,
+}
+
+/// Features a pump model supports beyond infusing
+///
+/// Reported by ```PumpDriver::capabilities```, so the pump widget can
+/// hide controls the pump has no use for. Drivers for pump families
+/// with several models ask the pump which one it is, see e.g.
+/// ```ChemyxDriver::discover```.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Can pull liquid back into the syringe
+    pub withdraw: bool,
+    /// Can run stored multi-step programs
+    pub programs: bool,
+    /// Has a pressure sensor
+    pub pressure: bool,
+}
+
+impl Capabilities {
+    /// An infuse/withdraw pump with no extras
+    pub const BASIC: Capabilities = Capabilities {
+        withdraw: true,
+        programs: false,
+        pressure: false,
+    };
+
+    /// An infuse-only pump
+    pub const INFUSE_ONLY: Capabilities = Capabilities {
+        withdraw: false,
+        programs: false,
+        pressure: false,
+    };
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::BASIC
+    }
+}
+
+/// Commands understood by a syringe pump
+///
+/// Each call talks to the pump and waits for its reply, so drivers
+/// for slow links should be polled sparingly, see ```DriverLink```.
+pub trait PumpDriver {
+    /// Syringe inner diameter, from which the pump works out volumes
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError>;
+
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError>;
+
+    /// Volume to move in each run, ```None``` to run until stopped
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError>;
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError>;
+
+    fn stop(&mut self) -> Result<(), DriverError>;
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError>;
+
+    fn totals(&mut self) -> Result<Totals, DriverError>;
+
+    /// What this pump can do, as known without talking to it
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::BASIC
+    }
+
+    /// Zero the dispensed and withdrawn totals
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        Err(DriverError::Unsupported("clearing totals"))
+    }
+}
+
+/// A driver shared with the host, e.g. to inspect a ```MockDriver```
+/// after binding it through a ```DriverLink```
+impl<D: PumpDriver + ?Sized> PumpDriver for Rc<RefCell<D>> {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        self.borrow_mut().set_diameter(diameter_mm)
+    }
+
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        self.borrow_mut().set_rate(rate)
+    }
+
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        self.borrow_mut().set_volume(volume)
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        self.borrow_mut().run(direction)
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.borrow_mut().stop()
+    }
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        self.borrow_mut().status()
+    }
+
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        self.borrow_mut().totals()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.borrow().capabilities()
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.borrow_mut().clear_totals()
+    }
+}
+
+/// A driver bound to a ```PumpData```
+///
+/// Keeps what was last sent to the pump, so the diameter and rate
+/// are only sent when they change, and limits how often the pump
+/// status is polled. After a run fails to start, the next attempt
+/// waits for ```poll_interval``` too.
+pub struct DriverLink {
+    driver: Box<dyn PumpDriver>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    last_failure: Option<Instant>,
+    sent_diameter: Option<f32>,
+    sent_rate: Option<FlowRate>,
+    handled_until: f64,
+    held: Option<PumpButton>,
+}
+
+impl fmt::Debug for DriverLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriverLink")
+            .field("poll_interval", &self.poll_interval)
+            .field("last_poll", &self.last_poll)
+            .field("last_failure", &self.last_failure)
+            .field("sent_diameter", &self.sent_diameter)
+            .field("sent_rate", &self.sent_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DriverLink {
+    pub fn new(driver: impl PumpDriver + 'static) -> Self {
+        DriverLink {
+            driver: Box::new(driver),
+            poll_interval: Duration::from_millis(250),
+            last_poll: None,
+            last_failure: None,
+            sent_diameter: None,
+            sent_rate: None,
+            handled_until: f64::NEG_INFINITY,
+            held: None,
+        }
+    }
+
+    /// Shortest time between status polls, and between a failed run
+    /// and the next attempt, 250 ms by default
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn driver(&self) -> &dyn PumpDriver {
+        self.driver.as_ref()
+    }
+
+    pub fn driver_mut(&mut self) -> &mut dyn PumpDriver {
+        self.driver.as_mut()
+    }
+
+    /// Send ```diameter_mm``` unless the pump already has it.
+    pub fn send_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        if self.sent_diameter != Some(diameter_mm) {
+            self.driver.set_diameter(diameter_mm)?;
+            self.sent_diameter = Some(diameter_mm);
+        }
+        Ok(())
+    }
+
+    /// Send ```rate``` unless the pump already has it.
+    pub fn send_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        if self.sent_rate != Some(rate) {
+            self.driver.set_rate(rate)?;
+            self.sent_rate = Some(rate);
+        }
+        Ok(())
+    }
+
+    /// Whether ```poll_interval``` has passed since the last poll, as of ```now```
+    pub fn poll_due(&self, now: Instant) -> bool {
+        self.last_poll
+            .is_none_or(|last| now.duration_since(last) >= self.poll_interval)
+    }
+
+    /// Whether a run may be started as of ```now```, false for
+    /// ```poll_interval``` after one failed
+    pub fn retry_due(&self, now: Instant) -> bool {
+        self.last_failure
+            .is_none_or(|last| now.duration_since(last) >= self.poll_interval)
+    }
+
+    /// Whether the pump has been polled since the link was made
+    pub fn polled(&self) -> bool {
+        self.last_poll.is_some()
+    }
+
+    /// Send the rate and diameter if they changed, then run.
+    ///
+    /// A failure is remembered as of ```now```, see ```retry_due```.
+    pub fn start(
+        &mut self,
+        now: Instant,
+        rate: FlowRate,
+        diameter_mm: Option<f32>,
+        direction: FlowDirection,
+    ) -> Result<(), DriverError> {
+        let result = self.send_rate(rate).and_then(|()| {
+            if let Some(diameter_mm) = diameter_mm {
+                self.send_diameter(diameter_mm)?;
+            }
+            self.driver.run(direction)
+        });
+        self.last_failure = result.is_err().then_some(now);
+        result
+    }
+
+    /// Ask for status and totals, or ```None``` if polled too recently.
+    pub fn poll(&mut self, now: Instant) -> Option<Result<(PumpStatus, Totals), DriverError>> {
+        if !self.poll_due(now) {
+            return None;
+        }
+        self.last_poll = Some(now);
+        Some(
+            self.driver
+                .status()
+                .and_then(|status| Ok((status, self.driver.totals()?))),
+        )
+    }
+
+    /// Forget what was sent, e.g. after the pump was power cycled.
+    pub fn resend(&mut self) {
+        self.sent_diameter = None;
+        self.sent_rate = None;
+    }
+
+    /// Time of the last event sent to the pump
+    pub(crate) fn handled_until(&self) -> f64 {
+        self.handled_until
+    }
+
+    /// Mark events up to ```time``` as handled
+    pub(crate) fn handle_until(&mut self, time: f64) {
+        self.handled_until = self.handled_until.max(time);
+    }
+
+    /// Whether a run was already sent for the current press of ```button```
+    pub(crate) fn is_held(&self, button: PumpButton) -> bool {
+        self.held == Some(button)
+    }
+
+    pub(crate) fn hold(&mut self, button: PumpButton) {
+        self.held = Some(button);
+    }
+
+    pub(crate) fn release(&mut self) {
+        self.held = None;
+    }
+}
@@ -0,0 +1,408 @@
+use std::io::{Read, Write};
+
+use super::{DriverError, PumpDriver, PumpStatus, Totals};
+use crate::{FlowDirection, FlowRate, RunState, Volume};
+
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+/// Longest reply accepted before giving up on finding its end
+const MAX_REPLY_LEN: usize = 64;
+
+/// Driver for New Era NE-1000 and NE-500 series pumps
+///
+/// Speaks the basic RS-232 protocol: commands such as ```DIA```,
+/// ```RAT```, ```VOL```, ```DIR```, ```RUN```, ```STP``` and ```DIS```
+/// end with a carriage return, and each reply is framed by STX and
+/// ETX around the pump address, a status prompt and any data.
+///
+/// Pumps on a network daisy chain share one port and are told apart
+/// by their address, see ```SharedTransport```. Without an address
+/// commands go to the pump at address 0.
+///
+/// Alarms such as a motor stall are reported by the pump in place of
+/// the next reply. A command answered with an alarm fails with
+/// ```DriverError::Alarm```, and the alarm is also kept for the
+/// following ```status``` call.
+#[derive(Debug)]
+pub struct NewEraDriver<T> {
+    transport: T,
+    address: Option<u8>,
+    state: RunState,
+    direction: FlowDirection,
+    alarm: Option<String>,
+}
+
+impl<T: Read + Write> NewEraDriver<T> {
+    pub fn new(transport: T) -> Self {
+        NewEraDriver {
+            transport,
+            address: None,
+            state: RunState::Idle,
+            direction: FlowDirection::Dispense,
+            alarm: None,
+        }
+    }
+
+    /// Network address of the pump, 0 to 99
+    ///
+    /// # Panics
+    ///
+    /// If ```address``` is above 99.
+    pub fn address(mut self, address: u8) -> Self {
+        assert!(address <= 99, "pump address must be 0 to 99, got {address}");
+        self.address = Some(address);
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Send ```command``` and return the data part of the reply.
+    pub fn command(&mut self, command: &str) -> Result<String, DriverError> {
+        let request = match self.address {
+            Some(address) => format!("{address}{command}\r"),
+            None => format!("{command}\r"),
+        };
+        self.transport.write_all(request.as_bytes())?;
+        self.transport.flush()?;
+
+        let reply = self.read_reply()?;
+        self.parse_reply(&reply)
+    }
+
+    /// Read bytes up to and including ETX.
+    fn read_reply(&mut self) -> Result<String, DriverError> {
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            if self.transport.read(&mut byte)? == 0 {
+                return Err(DriverError::Timeout);
+            }
+            match byte[0] {
+                ETX => break,
+                STX => reply.clear(),
+                b => reply.push(b),
+            }
+            if reply.len() > MAX_REPLY_LEN {
+                return Err(DriverError::Protocol(
+                    String::from_utf8_lossy(&reply).into_owned(),
+                ));
+            }
+        }
+        Ok(String::from_utf8_lossy(&reply).into_owned())
+    }
+
+    /// Check the address, track the status prompt and return the data.
+    ///
+    /// A reply is two address digits, then a prompt: ```I``` infusing,
+    /// ```W``` withdrawing, ```S``` stopped, ```P``` paused, ```T```
+    /// timed pause, ```U``` waiting for a trigger, ```X``` purging, or
+    /// ```A?``` and an alarm code. Rejected commands add ```?``` and a
+    /// reason to the prompt.
+    fn parse_reply(&mut self, reply: &str) -> Result<String, DriverError> {
+        let protocol = || DriverError::Protocol(reply.to_string());
+        let (address, rest) = reply.split_at_checked(2).ok_or_else(protocol)?;
+        let address: u8 = address.parse().map_err(|_| protocol())?;
+        if address != self.address.unwrap_or(0) {
+            return Err(protocol());
+        }
+
+        let mut chars = rest.chars();
+        let prompt = chars.next().ok_or_else(protocol)?;
+        let data = chars.as_str();
+        if prompt == 'A' {
+            let code = data.strip_prefix('?').ok_or_else(protocol)?;
+            let (state, alarm) = match code {
+                "S" => (RunState::Stalled, "Motor stalled"),
+                "R" => (RunState::Error, "Pump was reset"),
+                "T" => (RunState::Error, "Communications timeout"),
+                "E" => (RunState::Error, "Program error"),
+                "O" => (RunState::Error, "Program phase out of range"),
+                _ => (RunState::Error, "Unknown alarm"),
+            };
+            self.state = state;
+            self.alarm = Some(alarm.to_string());
+            return Err(DriverError::Alarm(alarm.to_string()));
+        }
+
+        self.state = match prompt {
+            'I' => {
+                self.direction = FlowDirection::Dispense;
+                RunState::Running
+            }
+            'W' => {
+                self.direction = FlowDirection::Withdraw;
+                RunState::Running
+            }
+            'X' => RunState::Running,
+            'S' => RunState::Idle,
+            'P' | 'T' | 'U' => RunState::Paused,
+            _ => return Err(protocol()),
+        };
+
+        match data.strip_prefix('?') {
+            Some(reason) => Err(DriverError::Rejected(
+                match reason {
+                    "" => "unrecognized command",
+                    "NA" => "not applicable now",
+                    "OOR" => "out of range",
+                    "COM" => "invalid packet",
+                    "IGN" => "ignored",
+                    reason => reason,
+                }
+                .to_string(),
+            )),
+            None => Ok(data.to_string()),
+        }
+    }
+}
+
+/// Format a value with the pump's four significant digits, ```None``` if too large
+///
+/// Rounding can carry into another digit, e.g. 9.9996 to "10.000",
+/// so the decimals are dropped until at most four digits are left.
+fn ne_number(value: f32) -> Option<String> {
+    if !(0.0..9999.5).contains(&value) {
+        return None;
+    }
+    (0..=3usize)
+        .rev()
+        .map(|decimals| format!("{value:.decimals$}"))
+        .find(|text| text.chars().filter(char::is_ascii_digit).count() <= 4)
+}
+
+/// Parse the ```DIS``` reply, e.g. "I1.250W0.000ML"
+fn parse_totals(data: &str) -> Option<Totals> {
+    let (volumes, unit) = data.split_at_checked(data.len().checked_sub(2)?)?;
+    let (dispensed, withdrawn) = volumes.strip_prefix('I')?.split_once('W')?;
+    let volume = |text: &str| -> Option<Volume> {
+        let value: f32 = text.parse().ok()?;
+        match unit {
+            "UL" => Some(Volume::from_ul(value)),
+            "ML" => Some(Volume::from_ml(value)),
+            _ => None,
+        }
+    };
+    Some(Totals {
+        dispensed: volume(dispensed)?,
+        withdrawn: volume(withdrawn)?,
+    })
+}
+
+impl<T: Read + Write> PumpDriver for NewEraDriver<T> {
+    fn set_diameter(&mut self, diameter_mm: f32) -> Result<(), DriverError> {
+        let diameter = ne_number(diameter_mm).ok_or(DriverError::Unsupported("diameter"))?;
+        self.command(&format!("DIA {diameter}")).map(drop)
+    }
+
+    /// Sent in whichever of uL/h, uL/min, mL/h and mL/min keeps the
+    /// most digits.
+    fn set_rate(&mut self, rate: FlowRate) -> Result<(), DriverError> {
+        let (rate, units) = [
+            (rate.as_ul_per_hour(), "UH"),
+            (rate.as_ul_per_min(), "UM"),
+            (rate.as_ml_per_hour(), "MH"),
+            (rate.as_ml_per_min(), "MM"),
+        ]
+        .into_iter()
+        .find_map(|(value, units)| Some((ne_number(value)?, units)))
+        .ok_or(DriverError::Unsupported("flow rate"))?;
+        self.command(&format!("RAT {rate} {units}")).map(drop)
+    }
+
+    /// A volume of 0 makes the pump run until stopped.
+    fn set_volume(&mut self, volume: Option<Volume>) -> Result<(), DriverError> {
+        let volume = volume.unwrap_or(Volume::ZERO);
+        let (units, value) = if volume.as_ul() < 9999.5 {
+            ("UL", volume.as_ul())
+        } else {
+            ("ML", volume.as_ml())
+        };
+        let value = ne_number(value).ok_or(DriverError::Unsupported("volume"))?;
+        self.command(&format!("VOL {units}"))?;
+        self.command(&format!("VOL {value}")).map(drop)
+    }
+
+    fn run(&mut self, direction: FlowDirection) -> Result<(), DriverError> {
+        self.command(match direction {
+            FlowDirection::Dispense => "DIR INF",
+            FlowDirection::Withdraw => "DIR WDR",
+        })?;
+        self.command("RUN").map(drop)
+    }
+
+    fn stop(&mut self) -> Result<(), DriverError> {
+        self.command("STP").map(drop)
+    }
+
+    fn status(&mut self) -> Result<PumpStatus, DriverError> {
+        let earlier = self.alarm.take().map(|alarm| (self.state, alarm));
+        // An empty command is answered with just the prompt
+        match self.command("") {
+            Ok(_) | Err(DriverError::Alarm(_)) => {}
+            Err(error) => return Err(error),
+        }
+        let (state, alarm) = match (self.alarm.take(), earlier) {
+            (Some(alarm), _) => (self.state, Some(alarm)),
+            (None, Some((state, alarm))) => (state, Some(alarm)),
+            (None, None) => (self.state, None),
+        };
+        Ok(PumpStatus {
+            state,
+            direction: self.direction,
+            alarm,
+        })
+    }
+
+    fn totals(&mut self) -> Result<Totals, DriverError> {
+        let data = self.command("DIS")?;
+        parse_totals(&data).ok_or(DriverError::Protocol(data))
+    }
+
+    fn clear_totals(&mut self) -> Result<(), DriverError> {
+        self.command("CLD INF")?;
+        self.command("CLD WDR").map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ScriptedTransport, SharedTransport};
+
+    /// A reply framed by STX and ETX
+    fn frame(reply: &str) -> String {
+        format!("\u{2}{reply}\u{3}")
+    }
+
+    fn driver(script: &[(&str, &str)]) -> NewEraDriver<ScriptedTransport> {
+        let framed = script
+            .iter()
+            .map(|(request, reply)| (request, frame(reply)));
+        NewEraDriver::new(ScriptedTransport::from_script(framed))
+    }
+
+    #[test]
+    fn framing() {
+        let mut driver = driver(&[("DIA 14.57\r", "00S")]);
+        driver.set_diameter(14.57).unwrap();
+        assert!(driver.transport().is_done());
+
+        // Noise before STX is dropped
+        let transport = ScriptedTransport::new().expect("\r", "\u{0}xx\u{2}00I\u{3}");
+        let mut driver = NewEraDriver::new(transport);
+        let status = driver.status().unwrap();
+        assert_eq!(status.state, RunState::Running);
+        assert_eq!(status.direction, FlowDirection::Dispense);
+
+        // No ETX within the maximum reply length
+        let transport = ScriptedTransport::new().expect("\r", format!("\u{2}{}", "0".repeat(80)));
+        let mut driver = NewEraDriver::new(transport);
+        assert!(matches!(driver.status(), Err(DriverError::Protocol(_))));
+    }
+
+    #[test]
+    fn address_mismatch() {
+        let transport = ScriptedTransport::new().expect("3STP\r", frame("04S"));
+        let mut driver = NewEraDriver::new(transport).address(3);
+        assert!(matches!(driver.stop(), Err(DriverError::Protocol(_))));
+    }
+
+    #[test]
+    #[should_panic(expected = "0 to 99")]
+    fn address_out_of_range() {
+        let _ = NewEraDriver::new(ScriptedTransport::new()).address(100);
+    }
+
+    #[test]
+    fn alarms() {
+        for (code, state, alarm) in [
+            ("S", RunState::Stalled, "Motor stalled"),
+            ("R", RunState::Error, "Pump was reset"),
+            ("T", RunState::Error, "Communications timeout"),
+            ("E", RunState::Error, "Program error"),
+            ("O", RunState::Error, "Program phase out of range"),
+        ] {
+            let reply = format!("00A?{code}");
+            let mut driver = driver(&[("RUN\r", &reply), ("\r", "00S")]);
+            match driver.command("RUN") {
+                Err(DriverError::Alarm(text)) => assert_eq!(text, alarm),
+                other => panic!("{code}: {other:?}"),
+            }
+
+            // The alarm is reported once more by the next status
+            let status = driver.status().unwrap();
+            assert_eq!(status.state, state, "{code}");
+            assert_eq!(status.alarm.as_deref(), Some(alarm));
+        }
+    }
+
+    #[test]
+    fn rejections() {
+        let mut driver = driver(&[("RAT 1500 UM\r", "00S?OOR"), ("RUN\r", "00I?NA")]);
+        match driver.set_rate(FlowRate::from_ul_per_min(1500.0)) {
+            Err(DriverError::Rejected(reason)) => assert_eq!(reason, "out of range"),
+            other => panic!("{other:?}"),
+        }
+        match driver.command("RUN") {
+            Err(DriverError::Rejected(reason)) => assert_eq!(reason, "not applicable now"),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn totals() {
+        let mut driver = driver(&[
+            ("DIS\r", "00SI1.250W0.500ML"),
+            ("DIS\r", "00SI12.50W0.000UL"),
+        ]);
+        assert_eq!(
+            driver.totals().unwrap(),
+            Totals {
+                dispensed: Volume::from_ml(1.25),
+                withdrawn: Volume::from_ml(0.5),
+            }
+        );
+        assert_eq!(driver.totals().unwrap().dispensed, Volume::from_ul(12.5));
+        assert_eq!(parse_totals("I1.0W2.0GL"), None);
+        assert_eq!(parse_totals("1.0W2.0ML"), None);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(ne_number(4.61).as_deref(), Some("4.610"));
+        assert_eq!(ne_number(14.567).as_deref(), Some("14.57"));
+        assert_eq!(ne_number(9.9996).as_deref(), Some("10.00"));
+        assert_eq!(ne_number(99.996).as_deref(), Some("100.0"));
+        assert_eq!(ne_number(999.96).as_deref(), Some("1000"));
+        assert_eq!(ne_number(9999.6), None);
+        assert_eq!(ne_number(-1.0), None);
+    }
+
+    #[test]
+    fn shared_transport() {
+        let transport = SharedTransport::new(
+            ScriptedTransport::new()
+                .expect("1DIR INF\r", frame("01S"))
+                .expect("1RUN\r", frame("01I"))
+                .expect("2DIR WDR\r", frame("02S"))
+                .expect("2RUN\r", frame("02W"))
+                .expect("1STP\r", frame("01S")),
+        );
+        let mut first = NewEraDriver::new(transport.clone()).address(1);
+        let mut second = NewEraDriver::new(transport.clone()).address(2);
+        first.run(FlowDirection::Dispense).unwrap();
+        second.run(FlowDirection::Withdraw).unwrap();
+        first.stop().unwrap();
+        assert_eq!(first.state, RunState::Idle);
+        assert_eq!(second.state, RunState::Running);
+        assert_eq!(second.direction, FlowDirection::Withdraw);
+        assert!(transport.with(|transport| transport.is_done()));
+    }
+}
@@ -0,0 +1,147 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+/// One transport used by several drivers, e.g. a serial port with a
+/// daisy chain of pumps on it
+///
+/// Clones share the same transport. Each driver finishes its
+/// request and reply before the next driver gets a turn, so the
+/// pumps must all be driven from the same thread.
+#[derive(Debug)]
+pub struct SharedTransport<T> {
+    inner: Rc<RefCell<T>>,
+}
+
+impl<T> SharedTransport<T> {
+    pub fn new(transport: T) -> Self {
+        SharedTransport {
+            inner: Rc::new(RefCell::new(transport)),
+        }
+    }
+
+    /// Run ```f``` with the transport, e.g. to change its timeout.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.inner.borrow_mut())
+    }
+}
+
+impl<T> Clone for SharedTransport<T> {
+    fn clone(&self) -> Self {
+        SharedTransport {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Read> Read for SharedTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.borrow_mut().read(buf)
+    }
+}
+
+impl<T: Write> Write for SharedTransport<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.borrow_mut().flush()
+    }
+}
+
+/// A fake serial port that replays a scripted conversation
+///
+/// Each request written and flushed by a driver is checked against
+/// the next expected request, and the matching reply is then made
+/// available to read. An unexpected request fails with
+/// ```InvalidInput```, and reading with no reply pending times out
+/// like a real port with nothing to say.
+#[derive(Debug, Default)]
+pub struct ScriptedTransport {
+    script: VecDeque<(Vec<u8>, Vec<u8>)>,
+    request: Vec<u8>,
+    reply: VecDeque<u8>,
+    requests: Vec<Vec<u8>>,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer ```request``` with ```reply```, after the requests already expected.
+    pub fn expect(mut self, request: impl AsRef<[u8]>, reply: impl AsRef<[u8]>) -> Self {
+        self.script
+            .push_back((request.as_ref().to_vec(), reply.as_ref().to_vec()));
+        self
+    }
+
+    /// A transport answering each request of ```script``` with its reply, in order
+    pub fn from_script<Q: AsRef<[u8]>, R: AsRef<[u8]>>(
+        script: impl IntoIterator<Item = (Q, R)>,
+    ) -> Self {
+        script
+            .into_iter()
+            .fold(Self::new(), |transport, (request, reply)| {
+                transport.expect(request, reply)
+            })
+    }
+
+    /// Every expected request has been made
+    pub fn is_done(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> &[Vec<u8>] {
+        &self.requests
+    }
+}
+
+impl Read for ScriptedTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reply.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no scripted reply"));
+        }
+        let n = buf.len().min(self.reply.len());
+        for (byte, reply) in buf.iter_mut().zip(self.reply.drain(..n)) {
+            *byte = reply;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ScriptedTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.request.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let request = std::mem::take(&mut self.request);
+        self.requests.push(request.clone());
+        match self.script.pop_front() {
+            Some((expected, reply)) if expected == request => {
+                self.reply.extend(reply);
+                Ok(())
+            }
+            Some((expected, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "expected {:?}, got {:?}",
+                    String::from_utf8_lossy(&expected),
+                    String::from_utf8_lossy(&request)
+                ),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unexpected {:?} after end of script",
+                    String::from_utf8_lossy(&request)
+                ),
+            )),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use egui::{ImageSource, Theme};
+
+/// Where an icon is used on the pump panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IconRole {
+    Syringe,
+    SpeakerOn,
+    SpeakerOff,
+    Linked,
+    Unlinked,
+    FlowSettings,
+    PumpSettings,
+    Info,
+    Arrow,
+    /// Shown in place of the syringe barrel when no syringe is fitted
+    Pump,
+}
+
+/// Icons drawn by the pump widget
+///
+/// Each role has a built-in icon for dark and light mode. Host apps
+/// may register their own ```ImageSource``` per role, either for both
+/// themes or separately for each.
+///
+/// Menu bar icons sit on a bar filled with the text colour, so dark
+/// mode uses the dark icon variants and light mode the light ones.
+#[derive(Clone, Default)]
+pub struct IconSet {
+    dark: Vec<(IconRole, ImageSource<'static>)>,
+    light: Vec<(IconRole, ImageSource<'static>)>,
+}
+
+impl IconSet {
+    /// Built-in icons only
+    pub const DEFAULT: IconSet = IconSet {
+        dark: Vec::new(),
+        light: Vec::new(),
+    };
+
+    /// Use ```source``` for ```role``` in both dark and light mode.
+    pub fn set(self, role: IconRole, source: ImageSource<'static>) -> Self {
+        self.set_themed(role, source.clone(), source)
+    }
+
+    /// Use separate dark and light mode sources for ```role```.
+    pub fn set_themed(
+        mut self,
+        role: IconRole,
+        dark: ImageSource<'static>,
+        light: ImageSource<'static>,
+    ) -> Self {
+        self.dark.retain(|(r, _)| *r != role);
+        self.light.retain(|(r, _)| *r != role);
+        self.dark.push((role, dark));
+        self.light.push((role, light));
+        self
+    }
+
+    /// Icon for ```role``` in ```theme```, falling back to the built-in icon.
+    pub fn get(&self, role: IconRole, theme: Theme) -> ImageSource<'static> {
+        let registered = match theme {
+            Theme::Dark => &self.dark,
+            Theme::Light => &self.light,
+        };
+        registered
+            .iter()
+            .find(|(r, _)| *r == role)
+            .map_or_else(|| builtin(role, theme), |(_, source)| source.clone())
+    }
+}
+
+impl std::fmt::Debug for IconSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let roles = |icons: &[(IconRole, ImageSource<'static>)]| {
+            icons.iter().map(|(role, _)| *role).collect::<Vec<_>>()
+        };
+        f.debug_struct("IconSet")
+            .field("dark", &roles(&self.dark))
+            .field("light", &roles(&self.light))
+            .finish()
+    }
+}
+
+/// Icons shipped in ```assets/pics```
+fn builtin(role: IconRole, theme: Theme) -> ImageSource<'static> {
+    match (role, theme) {
+        (IconRole::Syringe, _) => egui::include_image!("../assets/pics/syringe.svg"),
+        (IconRole::SpeakerOn, Theme::Dark) => {
+            egui::include_image!("../assets/pics/speaker-high.svg")
+        }
+        (IconRole::SpeakerOn, Theme::Light) => {
+            egui::include_image!("../assets/pics/speaker-high-light.svg")
+        }
+        (IconRole::SpeakerOff, Theme::Dark) => {
+            egui::include_image!("../assets/pics/speaker-x.svg")
+        }
+        (IconRole::SpeakerOff, Theme::Light) => {
+            egui::include_image!("../assets/pics/speaker-x-light.svg")
+        }
+        (IconRole::Linked, Theme::Dark) => egui::include_image!("../assets/pics/link.svg"),
+        (IconRole::Linked, Theme::Light) => {
+            egui::include_image!("../assets/pics/link-light.svg")
+        }
+        (IconRole::Unlinked, Theme::Dark) => {
+            egui::include_image!("../assets/pics/link-break.svg")
+        }
+        (IconRole::Unlinked, Theme::Light) => {
+            egui::include_image!("../assets/pics/link-break-light.svg")
+        }
+        (IconRole::FlowSettings, Theme::Dark) => {
+            egui::include_image!("../assets/pics/sliders.svg")
+        }
+        (IconRole::FlowSettings, Theme::Light) => {
+            egui::include_image!("../assets/pics/sliders-light.svg")
+        }
+        (IconRole::PumpSettings, Theme::Dark) => {
+            egui::include_image!("../assets/pics/gear-six.svg")
+        }
+        (IconRole::PumpSettings, Theme::Light) => {
+            egui::include_image!("../assets/pics/gear-six-light.svg")
+        }
+        (IconRole::Info, _) => egui::include_image!("../assets/pics/info.svg"),
+        // Arrows sit on the panel background rather than the menu bar
+        (IconRole::Arrow, Theme::Dark) => {
+            egui::include_image!("../assets/pics/triangle-light.svg")
+        }
+        (IconRole::Arrow, Theme::Light) => egui::include_image!("../assets/pics/triangle.svg"),
+        (IconRole::Pump, _) => egui::include_image!("../assets/pics/pump.svg"),
+    }
+}
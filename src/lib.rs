@@ -1,7 +1,47 @@
 use std::f32::consts::PI;
+use std::hash::Hash;
 
-use egui::{pos2, vec2, Color32, CornerRadius, FontId, Id, Rect, Stroke, StrokeKind, Vec2};
-use egui::{Response, Ui, Widget};
+pub mod bank;
+pub mod config;
+pub mod constants;
+pub mod driver;
+pub mod icons;
+pub mod run_state;
+pub mod simulator;
+pub mod syringe;
+pub mod units;
+
+pub use bank::{PumpBank, PumpBankResponse};
+pub use config::{PumpConfig, PumpConfigBuilder, PumpLayout};
+pub use driver::{
+    Capabilities, ChemyxDriver, DriverError, DriverLink, HarvardDriver, LegatoDriver, MockDriver,
+    NewEraDriver, PumpDriver, PumpStatus, ScriptedTransport, SharedTransport, Totals,
+};
+pub use icons::{IconRole, IconSet};
+pub use run_state::{IllegalTransition, RunState, RunStateLog, RunStateTransition};
+pub use simulator::PumpSimulator;
+pub use syringe::{
+    builtin_syringe, SyringeLibrary, SyringeMaterial, SyringeSpec, BUILTIN_SYRINGES,
+};
+pub use units::{format_duration, parse_duration, FlowRate, ParseUnitError, Volume};
+
+use constants::MENU_BAR_HEIGHT;
+use egui::{pos2, vec2, Color32, CornerRadius, FontId, Id, Pos2, Rect, Stroke, StrokeKind, Vec2};
+use egui::{
+    CursorIcon, Painter, PopupCloseBehavior, Response, Sense, Theme, Ui, UiBuilder, Widget,
+};
+
+/// Unscaled pump panel size, see ```PumpLayout``` for what goes where
+pub const PANEL_SIZE: Vec2 = Vec2::new(300.0, 200.0);
+
+/// Below this scale the panel hides its secondary icons and labels
+pub const COMPACT_SCALE: f32 = 0.7;
+
+/// Config used until one is attached with ```Pump::config```
+static DEFAULT_CONFIG: PumpConfig = PumpConfig::DEFAULT;
+
+/// Smallest font size used when the panel is scaled down
+const MIN_FONT_SIZE: f32 = 8.0;
 
 // This struct is the data structure stored in the
 // pump hashmap and holds all pump info and status
@@ -11,13 +51,161 @@ use egui::{Response, Ui, Widget};
 #[derive(Default, Debug)]
 pub struct PumpData {
     pub name: String,
-    pub pitch: f32,
+    pub pitch: f32, // Hz, tone of the audible feedback switched by sound
     pub sound: bool,
     pub syringeset: Syringeset,
+    pub custom_syringe: Option<SyringeSpec>, // Used in place of the built-in spec, set by the picker
     pub linkset: Linkset,
     pub info: bool,
+    pub selected: bool, // Highlighted as the focused pump, see PumpBank
     pub uid: u32,
+    pub flow_rate: FlowRate,
+    pub dispensed: Volume,
+    pub withdrawn: Volume,
+    pub volume: Volume,           // Liquid left in the syringe barrel
+    pub direction: FlowDirection, // Plunger direction of the current or last run
+    pub run_state: RunStateLog,
+    pub driver: Option<DriverLink>, // Hardware behind this pump, see sync_driver
+    pub last_error: Option<String>,
     pub pump_portal: PumpPortal,
+    pub events: Vec<PumpEvent>,
+}
+
+impl PumpData {
+    /// The fitted syringe: ```custom_syringe``` if set, otherwise
+    /// the built-in spec for ```syringeset```.
+    pub fn syringe(&self) -> Option<&SyringeSpec> {
+        self.custom_syringe
+            .as_ref()
+            .or_else(|| self.syringeset.spec())
+    }
+
+    /// Send new dispense/withdraw presses to the bound driver and poll
+    /// its status back.
+    ///
+    /// Call once per frame after the pump widget has been shown and
+    /// before its events are drained. A click starts a run or stops
+    /// one going the same way, holding a button runs until release.
+    /// The flow rate is sent with each run, so a new rate takes effect
+    /// on the next run. Driver errors and pump alarms are shown as the
+    /// last error.
+    ///
+    /// Stops are always sent. A run that fails is not retried until
+    /// ```DriverLink::retry_due```, presses in the meantime are kept
+    /// until then unless a later stop makes them moot.
+    ///
+    /// The liquid left in the barrel follows the pump's totals.
+    pub fn sync_driver(&mut self) {
+        let diameter = self.syringe().map(|spec| spec.inner_diameter_mm);
+        let capacity = self.syringe().map(SyringeSpec::capacity);
+        let Some(link) = &mut self.driver else {
+            return;
+        };
+        let now = web_time::Instant::now();
+
+        // Button events not yet sent, the same frame's events share a time
+        let handled_until = link.handled_until();
+        let mut result = Ok(());
+        for event in self.events.iter().filter(|e| e.time > handled_until) {
+            let (button, run) = match &event.kind {
+                PumpEventKind::ButtonClicked(button) => {
+                    let same_run = self.run_state.state() == RunState::Running
+                        && self.direction == button.direction();
+                    (*button, !same_run)
+                }
+                // Only the first of a press runs, the pump may stop itself while held
+                PumpEventKind::ButtonHeld { button, .. } if !link.is_held(*button) => {
+                    (*button, true)
+                }
+                PumpEventKind::ButtonReleased { button, .. } => (*button, false),
+                _ => continue,
+            };
+
+            if !run {
+                link.release();
+                let stopped = link.driver_mut().stop();
+                if stopped.is_ok() {
+                    self.run_state.follow(RunState::Idle);
+                }
+                result = result.and(stopped);
+                link.handle_until(event.time);
+                continue;
+            }
+            if !link.retry_due(now) {
+                continue;
+            }
+            let direction = button.direction();
+            if self.run_state.state() != RunState::Running || self.direction != direction {
+                match link.start(now, self.flow_rate, diameter, direction) {
+                    Ok(()) => {
+                        self.run_state.follow(RunState::Running);
+                        self.direction = direction;
+                    }
+                    Err(error) => result = result.and(Err(error)),
+                }
+            }
+            if matches!(event.kind, PumpEventKind::ButtonHeld { .. }) {
+                link.hold(button);
+            }
+            link.handle_until(event.time);
+        }
+
+        let first_poll = !link.polled();
+        match link.poll(now) {
+            Some(Ok((status, totals))) => {
+                if self.run_state.state() != RunState::Error {
+                    self.run_state.follow(status.state);
+                }
+                self.direction = status.direction;
+
+                // Liquid leaves the barrel as it is dispensed and comes back
+                // as it is withdrawn. Totals that went down were cleared.
+                if !first_poll {
+                    let moved = |total: Volume, last: Volume| {
+                        if total >= last {
+                            total - last
+                        } else {
+                            total
+                        }
+                    };
+                    let volume = self.volume + moved(totals.withdrawn, self.withdrawn)
+                        - moved(totals.dispensed, self.dispensed);
+                    self.volume = volume.max(Volume::ZERO);
+                    if let Some(capacity) = capacity {
+                        self.volume = self.volume.min(capacity);
+                    }
+                }
+                self.dispensed = totals.dispensed;
+                self.withdrawn = totals.withdrawn;
+                if status.alarm.is_some() {
+                    self.last_error = status.alarm;
+                }
+            }
+            Some(Err(error)) => result = Err(error),
+            None => {}
+        }
+        if let Err(error) = result {
+            self.last_error = Some(error.to_string());
+        }
+    }
+
+    /// Take all pending events from this pump, oldest first.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, PumpEvent> {
+        self.events.drain(..)
+    }
+}
+
+/// Take all pending events from a collection of pumps, ordered by time.
+///
+/// Works with any iterator of pumps, e.g. ```pumps.values_mut()```
+/// for a ```HashMap<u32, PumpData>```.
+pub fn drain_all_events<'a>(pumps: impl IntoIterator<Item = &'a mut PumpData>) -> Vec<PumpEvent> {
+    let mut events: Vec<PumpEvent> = pumps
+        .into_iter()
+        .flat_map(|pump| pump.events.drain(..))
+        .collect();
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    events
 }
 
 // These become the shared references
@@ -26,11 +214,15 @@ pub struct PumpData {
 pub struct PumpPortal {
     pub dispense_button: PumpDispenseWithdrawState,
     pub withdraw_button: PumpDispenseWithdrawState,
+    pub dispense_held_for: f32,
+    pub withdraw_held_for: f32,
     pub menu_syringe_icon: OffClicked,
-    pub action: Actions,
 }
 
-/// Link set
+/// Syringe set
+///
+/// Standard nominal volumes, or ```Custom``` for any other volume.
+/// Custom syringes can be described in a ```SyringeLibrary```.
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum Syringeset {
     #[default]
@@ -49,6 +241,66 @@ pub enum Syringeset {
     UL25000,
     UL50000,
     UL100000, // 100 mL
+    Custom(Volume),
+}
+
+impl Syringeset {
+    /// Every syringe set, in order of volume
+    pub const ALL: [Syringeset; 15] = [
+        Syringeset::None,
+        Syringeset::UL10,
+        Syringeset::UL25,
+        Syringeset::UL50,
+        Syringeset::UL100,
+        Syringeset::UL250,
+        Syringeset::UL500,
+        Syringeset::UL1000,
+        Syringeset::UL2500,
+        Syringeset::UL3000,
+        Syringeset::UL5000,
+        Syringeset::UL10000,
+        Syringeset::UL25000,
+        Syringeset::UL50000,
+        Syringeset::UL100000,
+    ];
+
+    /// Nominal volume
+    pub fn volume(&self) -> Option<Volume> {
+        match self {
+            Syringeset::None => None,
+            Syringeset::UL10 => Some(Volume::from_ul(10.0)),
+            Syringeset::UL25 => Some(Volume::from_ul(25.0)),
+            Syringeset::UL50 => Some(Volume::from_ul(50.0)),
+            Syringeset::UL100 => Some(Volume::from_ul(100.0)),
+            Syringeset::UL250 => Some(Volume::from_ul(250.0)),
+            Syringeset::UL500 => Some(Volume::from_ul(500.0)),
+            Syringeset::UL1000 => Some(Volume::from_ul(1000.0)),
+            Syringeset::UL2500 => Some(Volume::from_ul(2500.0)),
+            Syringeset::UL3000 => Some(Volume::from_ul(3000.0)),
+            Syringeset::UL5000 => Some(Volume::from_ul(5000.0)),
+            Syringeset::UL10000 => Some(Volume::from_ul(10000.0)),
+            Syringeset::UL25000 => Some(Volume::from_ul(25000.0)),
+            Syringeset::UL50000 => Some(Volume::from_ul(50000.0)),
+            Syringeset::UL100000 => Some(Volume::from_ul(100000.0)),
+            Syringeset::Custom(volume) => Some(*volume),
+        }
+    }
+
+    /// The standard variant for ```volume```, or ```Custom``` if there is none
+    pub fn from_volume(volume: Volume) -> Self {
+        Syringeset::ALL
+            .into_iter()
+            .find(|set| set.volume() == Some(volume))
+            .unwrap_or(Syringeset::Custom(volume))
+    }
+
+    /// Human readable label scaled to nL, µL or mL, e.g. "2.5 mL"
+    pub fn label(&self) -> String {
+        match self.volume() {
+            Some(volume) => volume.to_string(),
+            None => "None".to_string(),
+        }
+    }
 }
 
 /// Link set
@@ -64,6 +316,55 @@ pub enum Linkset {
     F,
 }
 
+/// Default accent colours for link groups A to F
+pub const DEFAULT_LINK_COLORS: [Color32; 6] = [
+    Color32::from_rgb(230, 80, 80),
+    Color32::from_rgb(240, 150, 50),
+    Color32::from_rgb(220, 200, 40),
+    Color32::from_rgb(80, 190, 90),
+    Color32::from_rgb(70, 140, 230),
+    Color32::from_rgb(170, 100, 220),
+];
+
+impl Linkset {
+    /// Every link set, ```None``` first
+    pub const ALL: [Linkset; 7] = [
+        Linkset::None,
+        Linkset::A,
+        Linkset::B,
+        Linkset::C,
+        Linkset::D,
+        Linkset::E,
+        Linkset::F,
+    ];
+
+    /// Human readable label, e.g. "A"
+    pub fn label(&self) -> &'static str {
+        match self {
+            Linkset::None => "None",
+            Linkset::A => "A",
+            Linkset::B => "B",
+            Linkset::C => "C",
+            Linkset::D => "D",
+            Linkset::E => "E",
+            Linkset::F => "F",
+        }
+    }
+
+    /// Accent colour of this link group, ```None``` if unlinked
+    pub fn color(&self, link_colors: &[Color32; 6]) -> Option<Color32> {
+        match self {
+            Linkset::None => None,
+            Linkset::A => Some(link_colors[0]),
+            Linkset::B => Some(link_colors[1]),
+            Linkset::C => Some(link_colors[2]),
+            Linkset::D => Some(link_colors[3]),
+            Linkset::E => Some(link_colors[4]),
+            Linkset::F => Some(link_colors[5]),
+        }
+    }
+}
+
 /// Pump mouse interaction states
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum OffOn {
@@ -79,7 +380,12 @@ pub enum OffClicked {
     Clicked,
 }
 
-#[derive(Default, Debug, PartialEq)]
+/// Dispense/withdraw button state
+///
+/// A button goes ```None``` -> ```Pressed``` when the pointer goes down
+/// on it, ```Pressed``` -> ```Held``` once the hold threshold has passed,
+/// and back to ```None``` on release.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum PumpDispenseWithdrawState {
     #[default]
     None,
@@ -87,10 +393,38 @@ pub enum PumpDispenseWithdrawState {
     Held,
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub enum Actions {
+/// Dispense or withdraw button
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PumpButton {
+    Dispense,
+    Withdraw,
+}
+
+impl PumpButton {
+    /// Which way the pump runs while this button is used
+    pub fn direction(&self) -> FlowDirection {
+        match self {
+            PumpButton::Dispense => FlowDirection::Dispense,
+            PumpButton::Withdraw => FlowDirection::Withdraw,
+        }
+    }
+}
+
+/// Which way liquid moves through the syringe
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowDirection {
+    /// Plunger moves in, liquid leaves the syringe
     #[default]
-    None,
+    Dispense,
+    /// Plunger moves out, liquid is drawn in
+    Withdraw,
+}
+
+/// What happened in the pump widget
+#[derive(Debug, Clone, PartialEq)]
+pub enum PumpEventKind {
+    /// The panel background was clicked, outside any icon or button
+    PanelClicked,
     PumpClicked,
     MenuSyringeClicked,
     MenuSpeakerClicked,
@@ -98,10 +432,47 @@ pub enum Actions {
     MenuFlowSettingsClicked,
     MenuPumpSettingsClicked,
     MenuInfoClicked,
-    ButtonDispenseClicked,
-    ButtonWithdrawClicked,
-    ButtonDispenseHeld,
-    ButtonWithdrawHeld,
+    /// A new syringe was chosen from the built-in syringe popup
+    ///
+    /// Choosing a library syringe also sets ```PumpData::custom_syringe```,
+    /// so ```old``` and ```new``` can be equal.
+    SyringeChanged {
+        old: Syringeset,
+        new: Syringeset,
+    },
+    /// A new link group was chosen from the built-in linkset popup
+    LinksetChanged {
+        old: Linkset,
+        new: Linkset,
+    },
+    /// The pump was renamed in place by double-clicking its name
+    Renamed {
+        old: String,
+        new: String,
+    },
+    /// Short press, released before the hold threshold
+    ButtonClicked(PumpButton),
+    /// Emitted every frame while the button is held
+    ButtonHeld {
+        button: PumpButton,
+        held_for: f32,
+    },
+    /// A held button was let go
+    ButtonReleased {
+        button: PumpButton,
+        held_for: f32,
+    },
+}
+
+/// Event queued on ```PumpData::events``` by the pump widget
+///
+/// ```time``` is the egui input time (seconds) of the frame
+/// in which the event happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpEvent {
+    pub uid: u32,
+    pub time: f64,
+    pub kind: PumpEventKind,
 }
 
 /// Pump information store
@@ -109,20 +480,46 @@ pub enum Actions {
 /// ```mouse_state``` and ```color_state``` are references, used
 /// as state-sharing portals between widget and main code bases.
 ///
-/// ```img``` is the Pump image (e.g. PNG, SVG). Images are unknown
-/// to the widget so must be initialized within the main code base.
+/// Icons come from the attached ```PumpConfig```'s ```IconSet```,
+/// see ```PumpConfigBuilder::icons```.
+///
+/// The returned ```Response``` covers the whole pump panel and is keyed
+/// by ```PumpData::uid``` (or by ```id_salt``` if one is supplied), so
+/// many pumps can share a ui without Id clashes.
 #[must_use = "You should put this widget in a ui with `ui.add(widget);`"]
 pub struct Pump<'a> {
-    pub action: &'a mut Actions,
+    pub events: &'a mut Vec<PumpEvent>,
     pub dispense_state: &'a mut PumpDispenseWithdrawState,
     pub withdraw_state: &'a mut PumpDispenseWithdrawState,
+    pub dispense_held_for: &'a mut f32,
+    pub withdraw_held_for: &'a mut f32,
     pub menu_syringe_icon: &'a mut OffClicked,
     pub pitch: &'a mut f32,
     pub sound_state: &'a mut bool,
     pub syringeset: &'a mut Syringeset,
+    pub custom_syringe: &'a mut Option<SyringeSpec>,
     pub linkset: &'a mut Linkset,
     pub info: &'a mut bool,
     pub name: &'a mut String,
+    pub uid: u32,
+    pub selected: bool,
+    pub flow_rate: &'a FlowRate,
+    pub dispensed: &'a Volume,
+    pub withdrawn: &'a Volume,
+    pub volume: &'a Volume,
+    pub direction: &'a FlowDirection,
+    pub run_state: &'a RunStateLog,
+    pub capabilities: Capabilities,
+    pub last_error: &'a Option<String>,
+    pub id_salt: Option<Id>,
+    pub hold_threshold: f32,
+    pub syringe_popup: bool,
+    pub linkset_popup: bool,
+    pub config: &'a PumpConfig,
+    pub syringe_library: Option<&'a SyringeLibrary>,
+    pub max_name_len: usize,
+    pub scale: f32,
+    pub draggable: bool,
 }
 
 /// Default values for the Pump struct...
@@ -130,19 +527,143 @@ impl<'a> Pump<'a> {
     pub fn new(pump_data: &'a mut PumpData) -> Self {
         Pump {
             // TX: Inputs directed to main codebase
-            action: &mut pump_data.pump_portal.action,
+            events: &mut pump_data.events,
             // RX: Data originating from main codebase
             dispense_state: &mut pump_data.pump_portal.dispense_button,
             withdraw_state: &mut pump_data.pump_portal.withdraw_button,
+            dispense_held_for: &mut pump_data.pump_portal.dispense_held_for,
+            withdraw_held_for: &mut pump_data.pump_portal.withdraw_held_for,
             menu_syringe_icon: &mut pump_data.pump_portal.menu_syringe_icon,
             pitch: &mut pump_data.pitch,
             sound_state: &mut pump_data.sound,
             syringeset: &mut pump_data.syringeset,
+            custom_syringe: &mut pump_data.custom_syringe,
             linkset: &mut pump_data.linkset,
             info: &mut pump_data.info,
             name: &mut pump_data.name,
+            uid: pump_data.uid,
+            selected: pump_data.selected,
+            flow_rate: &pump_data.flow_rate,
+            dispensed: &pump_data.dispensed,
+            withdrawn: &pump_data.withdrawn,
+            volume: &pump_data.volume,
+            direction: &pump_data.direction,
+            run_state: &pump_data.run_state,
+            capabilities: pump_data
+                .driver
+                .as_ref()
+                .map_or(Capabilities::BASIC, |link| link.driver().capabilities()),
+            last_error: &pump_data.last_error,
+            id_salt: None,
+            hold_threshold: 0.4,
+            syringe_popup: true,
+            linkset_popup: true,
+            config: &DEFAULT_CONFIG,
+            syringe_library: None,
+            max_name_len: 16,
+            scale: 1.0,
+            draggable: false,
         }
     }
+
+    /// Use a caller-supplied salt for the widget Id instead of the pump ```uid```.
+    ///
+    /// The salt is combined with the parent ui Id, as with other egui widgets.
+    #[inline]
+    pub fn id_salt(mut self, id_salt: impl Hash) -> Self {
+        self.id_salt = Some(Id::new(id_salt));
+        self
+    }
+
+    /// Seconds a dispense/withdraw button must be pressed before it is ```Held```.
+    ///
+    /// Defaults to 0.4 s. Shorter presses are reported as clicks.
+    #[inline]
+    pub fn hold_threshold(mut self, seconds: f32) -> Self {
+        self.hold_threshold = seconds;
+        self
+    }
+
+    /// Show the built-in syringe chooser when the syringe icon is clicked.
+    ///
+    /// Enabled by default. ```MenuSyringeClicked``` is emitted either way,
+    /// so hosts with their own chooser can turn this off.
+    #[inline]
+    pub fn syringe_popup(mut self, syringe_popup: bool) -> Self {
+        self.syringe_popup = syringe_popup;
+        self
+    }
+
+    /// Show the built-in link group selector when the link icon is clicked.
+    ///
+    /// Enabled by default. ```MenuLinksetClicked``` is emitted either way.
+    #[inline]
+    pub fn linkset_popup(mut self, linkset_popup: bool) -> Self {
+        self.linkset_popup = linkset_popup;
+        self
+    }
+
+    /// Attach a ```PumpConfig``` overriding colours, fonts and geometry.
+    #[inline]
+    pub fn config(mut self, config: &'a PumpConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Offer the syringes in ```library``` in the syringe picker.
+    ///
+    /// Also used to look up the spec of a ```Syringeset::Custom``` volume.
+    #[inline]
+    pub fn syringe_library(mut self, library: &'a SyringeLibrary) -> Self {
+        self.syringe_library = Some(library);
+        self
+    }
+
+    /// Show only the controls a pump with ```capabilities``` can use.
+    ///
+    /// Taken from the bound driver by default, see ```PumpData::driver```.
+    /// The withdraw arrow is hidden on infuse-only pumps.
+    #[inline]
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Maximum number of characters in a name entered by inline rename.
+    ///
+    /// Defaults to 16, which fits the menu bar at the default size.
+    #[inline]
+    pub fn max_name_len(mut self, max_name_len: usize) -> Self {
+        self.max_name_len = max_name_len;
+        self
+    }
+
+    /// Scale the whole panel, 1.0 being ```PANEL_SIZE```.
+    ///
+    /// Below ```COMPACT_SCALE``` the speaker, settings icons and
+    /// syringe/link labels are hidden to leave room for the rest.
+    #[inline]
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(0.1);
+        self
+    }
+
+    /// Scale the panel to fit within ```desired_size```, keeping its aspect ratio.
+    #[inline]
+    pub fn desired_size(self, desired_size: Vec2) -> Self {
+        let scale = (desired_size / PANEL_SIZE).min_elem();
+        self.scale(scale)
+    }
+
+    /// Let the pump be dragged by its menu bar.
+    ///
+    /// The returned ```Response``` then reports ```dragged()``` etc.,
+    /// e.g. for drag-and-drop reordering in a ```PumpBank```.
+    #[inline]
+    pub fn draggable(mut self, draggable: bool) -> Self {
+        self.draggable = draggable;
+        self
+    }
 }
 
 // ==================================================================
@@ -154,103 +675,164 @@ impl<'a> Pump<'a> {
 ///
 impl Widget for Pump<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
-        let menu_bar_color = ui.style().visuals.text_color();
-        let menu_items_color = ui.style().visuals.extreme_bg_color;
+        let cfg = self.config;
+        let visuals = &ui.style().visuals;
+        let menu_bar_color = cfg.menu_bar_color(visuals);
+        let menu_items_color = cfg.menu_items_color(visuals);
+        let frame_fill_hover = cfg.frame_fill_hover(visuals);
+        let frame_stroke_color = cfg.frame_stroke_color(visuals);
+        let frame_stroke_color_hover = cfg.frame_stroke_color_hover(visuals);
+        let selected_color = cfg.selected_color(visuals);
+        let text_color = cfg.text_color(visuals);
+        let label_color = cfg.label_color(visuals);
+        let arrow_color = cfg.arrow_color(visuals);
+        let error_color = cfg.error_color(visuals);
 
-        // Pump panel rectangle
-        let pump_panel = Vec2::new(300.0, 200.0); // Fixed size per panel
+        // Icon variants follow the current theme
+        let theme = Theme::from_dark_mode(visuals.dark_mode);
+        let icon = |role| cfg.icons().get(role, theme);
+        let link_color = self.linkset.color(cfg.link_colors());
 
-        // Allocate pump panel
-        let (response, painter) = ui.allocate_painter(pump_panel, egui::Sense::hover());
-        let rect = response.rect;
+        // Pump panel rectangle, laid out at PANEL_SIZE then scaled
+        let scale = self.scale;
+        let layout = cfg.layout();
+        let pump_panel = (PANEL_SIZE + vec2(0.0, cfg.menu_bar_extra())) * scale;
+        let font = |size: f32| FontId::proportional((size * scale).max(MIN_FONT_SIZE));
 
-        // name text (Pos2)
-        let name_text_pos = rect.min + vec2(6.0, 5.0);
+        // Menu bar items are centred vertically in the menu bar
+        let menu_offset = vec2(0.0, (cfg.menu_bar_height() - MENU_BAR_HEIGHT) / 2.0) * scale;
 
-        // Syringe rectangle
-        let syringe_icon_rect = Rect {
-            min: rect.min + vec2(80.0, 4.0),
-            max: rect.min + vec2(96.0, 20.0),
-        };
+        // Everything below the menu bar moves down with it
+        let body_offset = vec2(0.0, cfg.menu_bar_extra()) * scale;
 
-        // Syringe text (Pos2)
-        let syringe_text_pos = rect.min + vec2(100.0, 5.0);
+        // Secondary icons and labels are hidden on small panels
+        let compact = scale < COMPACT_SCALE;
 
-        // Speaker SVG rectangle
-        let speaker_rect = Rect {
-            min: rect.min + vec2(240.0, 2.0),
-            max: rect.min + vec2(260.0, 22.0),
+        // Pump panel Id, stable across frames
+        let id = match self.id_salt {
+            Some(id_salt) => ui.make_persistent_id(id_salt),
+            None => Id::new(("egui_widget_pump", self.uid)),
         };
 
-        // Link SVG
-        let link_rect = Rect {
-            min: rect.min + vec2(200.0, 2.0),
-            max: rect.min + vec2(220.0, 22.0),
+        // Events pushed this frame are stamped with the pump uid and input time
+        let events_before = self.events.len();
+        let uid = self.uid;
+        let now = ui.input(|i| i.time);
+        let event = |kind| PumpEvent {
+            uid,
+            time: now,
+            kind,
         };
 
-        // Info SVG
-        let info_rect = Rect {
-            min: rect.min + vec2(270.0, 2.0),
-            max: rect.min + vec2(290.0, 22.0),
-        };
+        // Allocate pump panel
+        let (_, rect) = ui.allocate_space(pump_panel);
+        let mut response = ui.interact(rect, id, Sense::click());
+        let painter = ui.painter_at(rect);
+        let mut hotspots = Vec::new();
 
-        // Left arrow button SVG
-        let left_arrow_rect = Rect {
-            min: rect.min + vec2(15.0, 60.0),
-            max: rect.min + vec2(45.0, 90.0),
-        };
+        // Layout positions and rectangles placed on the scaled panel
+        let at = |pos: Pos2| rect.min + pos.to_vec2() * scale;
+        let place =
+            |layout_rect: Rect| Rect::from_min_max(at(layout_rect.min), at(layout_rect.max));
 
-        // Right arrow button SVG
-        let right_arrow_rect = Rect {
-            min: rect.min + vec2(255.0, 60.0),
-            max: rect.min + vec2(285.0, 90.0),
-        };
+        // Menu bar items
+        let name_text_pos = at(layout.name) + menu_offset;
+        let syringe_icon_rect = place(layout.syringe_icon).translate(menu_offset);
+        let syringe_text_pos = at(layout.syringe_text) + menu_offset;
+        let speaker_rect = place(layout.speaker).translate(menu_offset);
+        let flow_settings_rect = place(layout.flow_settings).translate(menu_offset);
+        let pump_settings_rect = place(layout.pump_settings).translate(menu_offset);
+        let link_rect = place(layout.link).translate(menu_offset);
+        let info_rect = place(layout.info).translate(menu_offset);
+        let led_center = at(layout.led_center) + menu_offset;
 
-        // Pump SVG
-        let pump_rect = Rect {
-            min: rect.min + vec2(40.0, 40.0),
-            max: rect.min + vec2(240.0, 100.0),
-        };
+        // Arrows and pump below the menu bar
+        let left_arrow_rect = place(layout.dispense_arrow).translate(body_offset);
+        let right_arrow_rect = place(layout.withdraw_arrow).translate(body_offset);
+        let pump_rect = place(layout.pump).translate(body_offset);
 
         // Name on pump text (Pos2)
         let name_on_pump_text_pos = pos2(
-            pump_rect.min.x + ((pump_rect.max.x - pump_rect.min.x) / 2.0),
-            pump_rect.max.y - 11.0,
+            pump_rect.center().x,
+            pump_rect.max.y - layout.name_on_pump * scale,
+        );
+
+        // Hover highlight of the icons and arrows
+        let hotspot_style = (
+            layout.hotspot_padding * scale,
+            layout.hotspot_rounding * scale,
         );
 
         // Device panel
-        painter.rect_filled(rect, 5.0, Color32::TRANSPARENT); // Background color
-                                                              // Border stroke
-        painter.rect_stroke(
-            rect,
-            4.0,
-            Stroke::new(2.0, menu_bar_color),
-            StrokeKind::Inside,
+        // Frame visual state, animated between hover, active and selected
+        let ctx = ui.ctx();
+        let hover_t = ctx.animate_bool(id.with("hover"), response.contains_pointer());
+        let active_t = ctx.animate_bool(
+            id.with("active"),
+            response.contains_pointer() && ui.input(|i| i.pointer.primary_down()),
+        );
+        let selected_t = ctx.animate_bool(id.with("selected"), self.selected);
+
+        let rounding = cfg.frame_rounding() * scale;
+        let frame_fill = cfg
+            .frame_fill()
+            .lerp_to_gamma(frame_fill_hover, hover_t)
+            .lerp_to_gamma(selected_color.gamma_multiply(0.15), selected_t * 0.5);
+        let frame_stroke = Stroke::new(
+            (cfg.frame_stroke_width() * scale).max(1.0) * (1.0 + 0.5 * active_t + 0.5 * selected_t),
+            link_color
+                .unwrap_or(frame_stroke_color)
+                .lerp_to_gamma(frame_stroke_color_hover, hover_t)
+                .lerp_to_gamma(selected_color, selected_t),
         );
+        painter.rect_filled(rect, rounding, frame_fill); // Background color
+        painter.rect_stroke(rect, rounding, frame_stroke, StrokeKind::Inside); // Border stroke
 
         // Menu bar
+        let menu_bar_rect = Rect {
+            min: rect.min,
+            max: rect.min + vec2(rect.width(), cfg.menu_bar_height() * scale),
+        };
         painter.rect_filled(
-            Rect {
-                min: rect.min,
-                max: rect.min + vec2(rect.width(), 24.0),
-            },
+            menu_bar_rect,
             CornerRadius {
-                nw: 4,
-                ne: 4,
+                nw: rounding.round() as u8,
+                ne: rounding.round() as u8,
                 sw: 0,
                 se: 0,
             },
             menu_bar_color,
         );
 
-        // Pump name
-        painter.text(
+        // Menu bar drag handle, below the menu bar hotspots
+        if self.draggable {
+            let drag_response = ui
+                .interact(menu_bar_rect, id.with("drag"), Sense::drag())
+                .on_hover_cursor(CursorIcon::Grab);
+            hotspots.push(drag_response);
+        }
+
+        // Pump name, hidden while being renamed
+        let rename_id = id.with("rename");
+        let mut rename_buffer: Option<String> = ui.data(|d| d.get_temp(rename_id));
+        let name_rect = Rect::from_min_max(
             name_text_pos,
-            egui::Align2::LEFT_TOP,
-            &self.name,
-            FontId::proportional(12.0),
-            menu_items_color,
+            pos2(syringe_icon_rect.min.x - 4.0, syringe_icon_rect.max.y),
         );
+        let name_response = ui
+            .interact(name_rect, id.with("name"), Sense::click())
+            .on_hover_cursor(CursorIcon::Text);
+        if rename_buffer.is_none() {
+            painter.with_clip_rect(name_rect).text(
+                name_text_pos,
+                egui::Align2::LEFT_TOP,
+                &self.name,
+                font(cfg.menu_font_size()),
+                menu_items_color,
+            );
+        }
+        let mut start_rename = name_response.double_clicked();
+        hotspots.push(name_response);
 
         // // Add a label inside the rectangle
         // painter.text(
@@ -261,177 +843,821 @@ impl Widget for Pump<'_> {
         //     Color32::BLUE,
         // );
 
-        // Syringe SVG
-        egui::Image::new(egui::include_image!("../assets/pics/syringe.svg"))
-            .tint(menu_items_color)
-            .paint_at(ui, syringe_icon_rect);
-        // Manually check for mouse interaction
-
         // Syringe text
-        let syringe_text_rect = painter.text(
-            syringe_text_pos,
-            egui::Align2::LEFT_TOP,
-            match self.syringeset {
-                Syringeset::None => "None",
-                Syringeset::UL10 => "10 uL",
-                Syringeset::UL25 => "25 uL",
-                Syringeset::UL50 => "50 uL",
-                Syringeset::UL100 => "100 uL",
-                Syringeset::UL250 => "250 uL",
-                Syringeset::UL500 => "500 uL",
-                Syringeset::UL1000 => "1 mL",
-                Syringeset::UL2500 => "2.5 mL",
-                Syringeset::UL3000 => "3 mL",
-                Syringeset::UL5000 => "5 mL",
-                Syringeset::UL10000 => "10 mL",
-                Syringeset::UL25000 => "25 mL",
-                Syringeset::UL50000 => "50 mL",
-                Syringeset::UL100000 => "100 mL",
-            },
-            FontId::proportional(12.0),
+        let syringe_galley = painter.layout_no_wrap(
+            self.syringeset.label(),
+            font(cfg.menu_font_size()),
             menu_items_color,
         );
 
-        let syringe_icon_plus_text_rect = Rect {
-            min: syringe_icon_rect.min,
-            max: pos2(syringe_text_rect.max.x, syringe_icon_rect.max.y),
+        let syringe_icon_plus_text_rect = if compact {
+            syringe_icon_rect
+        } else {
+            Rect {
+                min: syringe_icon_rect.min,
+                max: pos2(
+                    syringe_text_pos.x + syringe_galley.size().x,
+                    syringe_icon_rect.max.y,
+                ),
+            }
         };
-        // Syringe icon + text mouse click detection
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            syringe_icon_plus_text_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            ui.label("Syringe menu icon clicked!");
-            *self.action = Actions::MenuSyringeClicked;
+
+        // Syringe icon + text
+        let syringe_response = hotspot(
+            ui,
+            &painter,
+            syringe_icon_plus_text_rect,
+            id.with("syringe"),
+            menu_items_color,
+            hotspot_style,
+        );
+        egui::Image::new(icon(IconRole::Syringe))
+            .tint(menu_items_color)
+            .paint_at(ui, syringe_icon_rect);
+        if !compact {
+            painter.galley(syringe_text_pos, syringe_galley, menu_items_color);
+        }
+        if syringe_response.clicked() {
+            self.events.push(event(PumpEventKind::MenuSyringeClicked));
         }
 
-        // Speaker SVG
-        if *self.sound_state {
-            egui::Image::new(egui::include_image!("../assets/pics/speaker-high.svg"))
+        // Syringe chooser popup
+        if self.syringe_popup {
+            let popup_id = id.with("syringe_popup");
+            if syringe_response.clicked() {
+                ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+            }
+            // Library syringes are told apart by manufacturer and model, not volume
+            let library = self.syringe_library;
+            let library_index = |spec: &SyringeSpec| {
+                library?
+                    .iter()
+                    .position(|s| s.manufacturer == spec.manufacturer && s.model == spec.model)
+            };
+            let current_set = *self.syringeset;
+            let current = match self.custom_syringe.as_ref().and_then(library_index) {
+                Some(index) => SyringeChoice::Library(index),
+                None => SyringeChoice::Standard(current_set),
+            };
+            let mut choices: Vec<SyringeChoice> = Syringeset::ALL
+                .into_iter()
+                .map(SyringeChoice::Standard)
+                .collect();
+            choices.extend((0..library.map_or(0, SyringeLibrary::len)).map(SyringeChoice::Library));
+            let library_spec = |index: usize| library.and_then(|l| l.iter().nth(index));
+            let chosen = choice_popup(
+                ui,
+                popup_id,
+                &syringe_response,
+                &choices,
+                current,
+                |choice| match *choice {
+                    SyringeChoice::Standard(set) => set.label(),
+                    SyringeChoice::Library(index) => library_spec(index)
+                        .map(|spec| format!("{}  {}", spec.volume, spec.label()))
+                        .unwrap_or_default(),
+                },
+            );
+            if let Some(choice) = chosen.filter(|&choice| choice != current) {
+                match choice {
+                    SyringeChoice::Standard(set) => {
+                        *self.custom_syringe = None;
+                        *self.syringeset = set;
+                    }
+                    SyringeChoice::Library(index) => {
+                        let spec = library_spec(index).cloned();
+                        *self.syringeset = spec.as_ref().map_or(Syringeset::None, |spec| {
+                            Syringeset::from_volume(spec.volume)
+                        });
+                        *self.custom_syringe = spec;
+                    }
+                }
+                self.events.push(event(PumpEventKind::SyringeChanged {
+                    old: current_set,
+                    new: *self.syringeset,
+                }));
+            }
+        }
+
+        hotspots.push(syringe_response);
+
+        // Secondary menu bar icons
+        if !compact {
+            // Speaker SVG
+            let speaker_response = hotspot(
+                ui,
+                &painter,
+                speaker_rect,
+                id.with("speaker"),
+                menu_items_color,
+                hotspot_style,
+            );
+            if *self.sound_state {
+                egui::Image::new(icon(IconRole::SpeakerOn))
+                    .tint(menu_items_color)
+                    .paint_at(ui, speaker_rect);
+            } else {
+                egui::Image::new(icon(IconRole::SpeakerOff))
+                    .tint(menu_items_color)
+                    .paint_at(ui, speaker_rect);
+            }
+            if speaker_response.clicked() {
+                self.events.push(event(PumpEventKind::MenuSpeakerClicked));
+            }
+
+            // Flow settings SVG
+            let flow_settings_response = hotspot(
+                ui,
+                &painter,
+                flow_settings_rect,
+                id.with("flow_settings"),
+                menu_items_color,
+                hotspot_style,
+            );
+            egui::Image::new(icon(IconRole::FlowSettings))
                 .tint(menu_items_color)
-                .paint_at(ui, speaker_rect);
-        } else {
-            egui::Image::new(egui::include_image!("../assets/pics/speaker-x.svg"))
+                .paint_at(ui, flow_settings_rect);
+            if flow_settings_response.clicked() {
+                self.events
+                    .push(event(PumpEventKind::MenuFlowSettingsClicked));
+            }
+
+            // Pump settings SVG
+            let pump_settings_response = hotspot(
+                ui,
+                &painter,
+                pump_settings_rect,
+                id.with("pump_settings"),
+                menu_items_color,
+                hotspot_style,
+            );
+            egui::Image::new(icon(IconRole::PumpSettings))
                 .tint(menu_items_color)
-                .paint_at(ui, speaker_rect);
-        }
-        // Manually check for mouse interaction
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            speaker_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            ui.label("Speaker menu icon clicked!");
-            *self.action = Actions::MenuSpeakerClicked;
+                .paint_at(ui, pump_settings_rect);
+            if pump_settings_response.clicked() {
+                self.events
+                    .push(event(PumpEventKind::MenuPumpSettingsClicked));
+            }
+
+            hotspots.push(speaker_response);
+            hotspots.push(flow_settings_response);
+            hotspots.push(pump_settings_response);
         }
 
+        // Link SVG
+        let link_response = hotspot(
+            ui,
+            &painter,
+            link_rect,
+            id.with("link"),
+            menu_items_color,
+            hotspot_style,
+        );
         if *self.linkset == Linkset::None {
-            egui::Image::new(egui::include_image!("../assets/pics/link-break-light.svg"))
+            egui::Image::new(icon(IconRole::Unlinked))
                 .tint(menu_items_color)
                 .paint_at(ui, link_rect);
         } else {
-            egui::Image::new(egui::include_image!("../assets/pics/link-light.svg"))
+            // Link group accent behind the icon
+            if let Some(link_color) = link_color {
+                painter.rect_filled(
+                    link_rect.shrink(1.0),
+                    layout.link_rounding * scale,
+                    link_color,
+                );
+            }
+            egui::Image::new(icon(IconRole::Linked))
                 .tint(menu_items_color)
                 .paint_at(ui, link_rect);
         }
-        // Manually check for mouse interaction
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            link_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            ui.label("Linkset menu icon clicked!");
-            *self.action = Actions::MenuLinksetClicked;
+        if link_response.clicked() {
+            self.events.push(event(PumpEventKind::MenuLinksetClicked));
+        }
+
+        // Link group selector popup
+        if self.linkset_popup {
+            let popup_id = id.with("linkset_popup");
+            if link_response.clicked() {
+                ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+            }
+            let current = *self.linkset;
+            let chosen = choice_popup(
+                ui,
+                popup_id,
+                &link_response,
+                &Linkset::ALL,
+                current,
+                |choice| choice.label().to_string(),
+            );
+            if let Some(new) = chosen {
+                if new != current {
+                    *self.linkset = new;
+                    self.events
+                        .push(event(PumpEventKind::LinksetChanged { old: current, new }));
+                }
+            }
         }
 
         // Link text
-        painter.text(
-            rect.min + vec2(220.0, 5.0),
-            egui::Align2::LEFT_TOP,
-            match self.linkset {
-                Linkset::None => "",
-                Linkset::A => "A",
-                Linkset::B => "B",
-                Linkset::C => "C",
-                Linkset::D => "D",
-                Linkset::E => "E",
-                Linkset::F => "F",
-            },
-            FontId::proportional(12.0),
+        if !compact && *self.linkset != Linkset::None {
+            painter.text(
+                at(layout.link_text) + menu_offset,
+                egui::Align2::LEFT_TOP,
+                self.linkset.label(),
+                font(cfg.menu_font_size()),
+                link_color.unwrap_or(menu_items_color),
+            );
+        }
+
+        hotspots.push(link_response);
+
+        // Info SVG
+        let info_response = hotspot(
+            ui,
+            &painter,
+            info_rect,
+            id.with("info"),
             menu_items_color,
+            hotspot_style,
         );
-
-        egui::Image::new(egui::include_image!("../assets/pics/info.svg"))
+        egui::Image::new(icon(IconRole::Info))
             .tint(menu_items_color)
             .paint_at(ui, info_rect);
-        // Manually check for mouse interaction
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            info_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            ui.label("Linkset menu icon clicked!");
-            *self.action = Actions::MenuInfoClicked;
+        if info_response.clicked() {
+            *self.info = !*self.info;
+            self.events.push(event(PumpEventKind::MenuInfoClicked));
         }
+        hotspots.push(info_response);
 
-        egui::Image::new(egui::include_image!("../assets/pics/triangle.svg"))
-            .tint(Color32::GRAY)
-            .rotate(PI / 6.0, Vec2::splat(0.5))
-            .paint_at(ui, left_arrow_rect);
-        // Manually check for mouse interaction
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            left_arrow_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            *self.action = Actions::ButtonDispenseClicked;
+        // Run state LED, blinking on a stall or error
+        let run_state = self.run_state.state();
+        let alarm = matches!(run_state, RunState::Stalled | RunState::Error);
+        let led_color = run_state.color(cfg.led_colors());
+        let led_color = if alarm && now % 1.0 > 0.5 {
+            led_color.gamma_multiply(0.3)
+        } else {
+            led_color
+        };
+        painter.circle(
+            led_center,
+            layout.led_radius * scale,
+            led_color,
+            Stroke::new(1.0, menu_items_color),
+        );
+        if alarm {
+            ui.ctx().request_repaint_after_secs(0.5);
         }
+        let led_response = ui.interact(
+            Rect::from_center_size(led_center, Vec2::splat(3.0 * layout.led_radius * scale)),
+            id.with("run_state"),
+            Sense::hover(),
+        );
+        let led_response = match self.run_state.since() {
+            Some(since) => led_response.on_hover_text(format!(
+                "{} for {}",
+                run_state,
+                format_duration(since.elapsed())
+            )),
+            None => led_response.on_hover_text(run_state.label()),
+        };
+        hotspots.push(led_response);
 
-        egui::Image::new(egui::include_image!("../assets/pics/triangle.svg"))
-            .tint(Color32::GRAY)
-            .rotate(-PI / 6.0, Vec2::splat(0.5))
-            .paint_at(ui, right_arrow_rect);
-        // Manually check for mouse interaction
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            right_arrow_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            *self.action = Actions::ButtonWithdrawClicked;
+        // Fitted syringe, from the custom spec, the library or the built-in table
+        let spec = self
+            .custom_syringe
+            .as_ref()
+            .or_else(|| match self.syringe_library {
+                Some(library) => library.spec(*self.syringeset),
+                None => self.syringeset.spec(),
+            });
+
+        // Controls are locked out until an error is cleared
+        let error = run_state == RunState::Error;
+
+        if *self.info || error {
+            let released = [
+                reset_button(
+                    PumpButton::Dispense,
+                    self.dispense_state,
+                    self.dispense_held_for,
+                ),
+                reset_button(
+                    PumpButton::Withdraw,
+                    self.withdraw_state,
+                    self.withdraw_held_for,
+                ),
+            ];
+            self.events
+                .extend(released.into_iter().flatten().map(event));
         }
 
-        egui::Image::new(egui::include_image!("../assets/pics/pump.svg")).paint_at(ui, pump_rect);
+        if *self.info {
+            // Info face replaces the arrows and pump
+            let syringe = match spec {
+                Some(spec) => format!(
+                    "{}, {} ({:.2} mm)",
+                    self.syringeset.label(),
+                    spec.label(),
+                    spec.inner_diameter_mm
+                ),
+                None => self.syringeset.label(),
+            };
+            let flow_rate = self.flow_rate.to_string();
+            let dispensed = self.dispensed.to_string();
+            let withdrawn = self.withdrawn.to_string();
+            let uid = self.uid.to_string();
+            let rows = [
+                ("UID", uid.as_str()),
+                ("Name", self.name.as_str()),
+                ("State", run_state.label()),
+                ("Syringe", syringe.as_str()),
+                ("Linkset", self.linkset.label()),
+                ("Flow rate", flow_rate.as_str()),
+                ("Dispensed", dispensed.as_str()),
+                ("Withdrawn", withdrawn.as_str()),
+            ];
 
-        // Manually check if the mouse is over the syringe_rect
-        let ctx = ui.ctx();
-        if ctx.pointer_latest_pos().map_or(false, |pos| {
-            pump_rect.contains(pos)
-                && ctx.input(|i| i.pointer.button_released(egui::PointerButton::Primary))
-        }) {
-            *self.action = Actions::PumpClicked;
+            let mut row_pos = at(layout.info_rows) + body_offset;
+            let value_offset = vec2(layout.info_value_offset, 0.0) * scale;
+            for (label, value) in rows {
+                painter.text(
+                    row_pos,
+                    egui::Align2::LEFT_TOP,
+                    label,
+                    font(cfg.info_font_size()),
+                    label_color,
+                );
+                painter.text(
+                    row_pos + value_offset,
+                    egui::Align2::LEFT_TOP,
+                    value,
+                    font(cfg.info_font_size()),
+                    text_color,
+                );
+                row_pos.y += layout.info_row_height * scale;
+            }
+
+            // Last error
+            painter.text(
+                row_pos,
+                egui::Align2::LEFT_TOP,
+                "Last error",
+                font(cfg.info_font_size()),
+                label_color,
+            );
+            match self.last_error {
+                Some(error) => painter.text(
+                    row_pos + value_offset,
+                    egui::Align2::LEFT_TOP,
+                    error,
+                    font(cfg.info_font_size()),
+                    error_color,
+                ),
+                None => painter.text(
+                    row_pos + value_offset,
+                    egui::Align2::LEFT_TOP,
+                    "None",
+                    font(cfg.info_font_size()),
+                    text_color,
+                ),
+            };
+        } else {
+            // Arrows are greyed out and ignore input in the error state
+            let mut controls_ui = ui.new_child(UiBuilder::new().max_rect(rect));
+            let arrow_color = if error {
+                controls_ui.disable();
+                arrow_color.gamma_multiply(0.3)
+            } else {
+                arrow_color
+            };
+
+            // Left arrow button SVG
+            let left_arrow_response = hotspot(
+                &controls_ui,
+                &painter,
+                left_arrow_rect,
+                id.with("dispense"),
+                arrow_color,
+                hotspot_style,
+            );
+            egui::Image::new(icon(IconRole::Arrow))
+                .tint(arrow_color)
+                .rotate(PI / 6.0, Vec2::splat(0.5))
+                .paint_at(ui, left_arrow_rect);
+            if let Some(kind) = update_button(
+                ui,
+                &left_arrow_response,
+                PumpButton::Dispense,
+                self.dispense_state,
+                self.dispense_held_for,
+                self.hold_threshold,
+            ) {
+                self.events.push(event(kind));
+            }
+
+            // Right arrow button SVG, hidden on infuse-only pumps
+            let right_arrow_response = if self.capabilities.withdraw {
+                let response = hotspot(
+                    &controls_ui,
+                    &painter,
+                    right_arrow_rect,
+                    id.with("withdraw"),
+                    arrow_color,
+                    hotspot_style,
+                );
+                egui::Image::new(icon(IconRole::Arrow))
+                    .tint(arrow_color)
+                    .rotate(-PI / 6.0, Vec2::splat(0.5))
+                    .paint_at(ui, right_arrow_rect);
+                if let Some(kind) = update_button(
+                    ui,
+                    &response,
+                    PumpButton::Withdraw,
+                    self.withdraw_state,
+                    self.withdraw_held_for,
+                    self.hold_threshold,
+                ) {
+                    self.events.push(event(kind));
+                }
+                Some(response)
+            } else {
+                if let Some(kind) = reset_button(
+                    PumpButton::Withdraw,
+                    self.withdraw_state,
+                    self.withdraw_held_for,
+                ) {
+                    self.events.push(event(kind));
+                }
+                None
+            };
+
+            // Syringe barrel, or the pump SVG if no syringe is fitted
+            let pump_response = hotspot(
+                ui,
+                &painter,
+                pump_rect,
+                id.with("pump"),
+                text_color,
+                hotspot_style,
+            );
+            let moving = (run_state == RunState::Running).then_some(*self.direction);
+            let capacity = spec
+                .map(SyringeSpec::capacity)
+                .or_else(|| self.syringeset.volume());
+            match capacity {
+                Some(capacity) => {
+                    let barrel_rect = Rect::from_min_max(
+                        pump_rect.min + layout.barrel.min.to_vec2() * scale,
+                        pump_rect.min + layout.barrel.max.to_vec2() * scale,
+                    );
+                    // A stalled or failed pump is tinted with the error colour
+                    let (outline_color, liquid_color) = if alarm {
+                        (
+                            error_color,
+                            cfg.liquid_color().lerp_to_gamma(error_color, 0.5),
+                        )
+                    } else {
+                        (text_color, cfg.liquid_color())
+                    };
+                    paint_syringe(
+                        &painter,
+                        barrel_rect,
+                        scale,
+                        (*self.volume / capacity).clamp(0.0, 1.0),
+                        moving.map(|direction| (direction, now)),
+                        outline_color,
+                        liquid_color,
+                    );
+                    if moving.is_some() {
+                        ui.ctx().request_repaint();
+                    }
+                }
+                None => egui::Image::new(icon(IconRole::Pump)).paint_at(ui, pump_rect),
+            }
+            if pump_response.clicked() {
+                self.events.push(event(PumpEventKind::PumpClicked));
+            }
+
+            // Pump name on pump SVG
+            let name_on_pump_rect = painter.text(
+                name_on_pump_text_pos,
+                egui::Align2::CENTER_CENTER,
+                // &self.name.to_uppercase(),
+                &self.name,
+                font(cfg.pump_font_size()),
+                text_color,
+            );
+            if pump_response.double_clicked()
+                && pump_response
+                    .interact_pointer_pos()
+                    .is_some_and(|pos| name_on_pump_rect.expand(4.0).contains(pos))
+            {
+                start_rename = true;
+            }
+
+            hotspots.push(left_arrow_response);
+            hotspots.extend(right_arrow_response);
+            hotspots.push(pump_response);
         }
 
-        // Pump name on pump SVG
-        painter.text(
-            name_on_pump_text_pos,
-            egui::Align2::CENTER_CENTER,
-            // &self.name.to_uppercase(),
-            &self.name,
-            FontId::proportional(10.0),
-            ui.style().visuals.text_color(),
-        );
+        // Inline rename over the menu bar name
+        if start_rename && rename_buffer.is_none() {
+            rename_buffer = Some(self.name.clone());
+        }
+        if let Some(buffer) = &mut rename_buffer {
+            let edit_response = ui.put(
+                name_rect.expand2(vec2(4.0, 3.0)),
+                egui::TextEdit::singleline(buffer)
+                    .char_limit(self.max_name_len)
+                    .font(font(cfg.menu_font_size()))
+                    .margin(vec2(2.0, 1.0)),
+            );
+            buffer.retain(is_valid_name_char);
+            if start_rename {
+                edit_response.request_focus();
+            } else if edit_response.lost_focus() {
+                // Enter or clicking away keeps the new name, Escape cancels
+                let new = buffer.trim().to_owned();
+                let cancelled = ui.input(|i| i.key_pressed(egui::Key::Escape));
+                if !cancelled && !new.is_empty() && new != *self.name {
+                    let old = std::mem::replace(self.name, new.clone());
+                    self.events.push(event(PumpEventKind::Renamed { old, new }));
+                }
+                rename_buffer = None;
+            }
+        }
+        ui.data_mut(|d| match rename_buffer {
+            Some(buffer) => d.insert_temp(rename_id, buffer),
+            None => d.remove::<String>(rename_id),
+        });
+
+        // Hotspots are on top, so the bare panel only sees background clicks
+        if response.clicked() {
+            self.events.push(event(PumpEventKind::PanelClicked));
+        }
+
+        if self.events.len() > events_before {
+            response.mark_changed();
+        }
+
+        // Panel response reflects interaction with any hotspot
+        hotspots
+            .into_iter()
+            .fold(response, |response, hotspot| response.union(hotspot))
+    }
+}
+
+/// Characters accepted by inline rename
+fn is_valid_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | '#' | '(' | ')' | '/')
+}
+
+/// An entry in the syringe picker
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SyringeChoice {
+    Standard(Syringeset),
+    /// Index into the ```SyringeLibrary```
+    Library(usize),
+}
+
+/// Show a popup list of choices below a hotspot.
+///
+/// The popup must be opened by the caller. Returns the clicked
+/// choice, closing the popup, or ```None``` if nothing was clicked.
+fn choice_popup<T: Copy + PartialEq>(
+    ui: &Ui,
+    popup_id: Id,
+    response: &Response,
+    choices: &[T],
+    current: T,
+    label: impl Fn(&T) -> String,
+) -> Option<T> {
+    let chosen = egui::popup_below_widget(
+        ui,
+        popup_id,
+        response,
+        PopupCloseBehavior::CloseOnClickOutside,
+        |ui| {
+            ui.set_min_width(80.0);
+            let mut chosen = None;
+            for &choice in choices {
+                if ui
+                    .selectable_label(choice == current, label(&choice))
+                    .clicked()
+                {
+                    chosen = Some(choice);
+                }
+            }
+            chosen
+        },
+    )
+    .flatten();
+    if chosen.is_some() {
+        ui.memory_mut(|mem| mem.close_popup());
+    }
+    chosen
+}
+
+/// Draw a syringe barrel filled to ```fill``` (0.0 to 1.0) with its plunger.
+///
+/// The tip points left, towards the dispense arrow. While ```moving```,
+/// chevrons scroll through the barrel in the flow direction, timed
+/// from the given input time.
+fn paint_syringe(
+    painter: &Painter,
+    barrel: Rect,
+    scale: f32,
+    fill: f32,
+    moving: Option<(FlowDirection, f64)>,
+    outline_color: Color32,
+    liquid_color: Color32,
+) {
+    let stroke = Stroke::new(1.5 * scale, outline_color);
+    let inner = barrel.shrink(1.5 * scale);
 
-        // Dummy code to generate Response
-        let image_rect = Rect {
-            min: pos2(0.0, 0.0),
-            max: pos2(300.0, 300.0),
+    // Tip and needle
+    let tip = Rect::from_min_max(
+        pos2(barrel.min.x - 10.0 * scale, barrel.center().y - 3.0 * scale),
+        pos2(barrel.min.x, barrel.center().y + 3.0 * scale),
+    );
+    painter.rect_stroke(tip, 1.0 * scale, stroke, StrokeKind::Inside);
+    painter.line_segment(
+        [
+            pos2(barrel.min.x - 22.0 * scale, barrel.center().y),
+            pos2(tip.min.x, barrel.center().y),
+        ],
+        stroke,
+    );
+
+    // Liquid up to the plunger stopper
+    let stopper_x = inner.min.x + fill * inner.width();
+    painter.rect_filled(
+        Rect::from_min_max(inner.min, pos2(stopper_x, inner.max.y)),
+        0.0,
+        liquid_color,
+    );
+
+    // Flow chevrons, pointing and scrolling the way liquid moves
+    if let Some((direction, time)) = moving {
+        let spacing = 12.0 * scale;
+        let offset = (time * 18.0 * scale as f64 % spacing as f64) as f32;
+        let (sign, offset) = match direction {
+            FlowDirection::Dispense => (-1.0, spacing - offset),
+            FlowDirection::Withdraw => (1.0, offset),
         };
+        let half = inner.height() * 0.3;
+        let chevrons = painter.with_clip_rect(inner);
+        let mut x = inner.min.x - spacing + offset;
+        while x < inner.max.x + spacing {
+            let tip = pos2(x + sign * half * 0.6, inner.center().y);
+            chevrons.line(
+                vec![
+                    pos2(x, inner.center().y - half),
+                    tip,
+                    pos2(x, inner.center().y + half),
+                ],
+                Stroke::new(1.5 * scale, outline_color.gamma_multiply(0.6)),
+            );
+            x += spacing;
+        }
+    }
+
+    // Barrel and finger flange
+    painter.rect_stroke(barrel, 2.0 * scale, stroke, StrokeKind::Inside);
+    painter.line_segment(
+        [
+            pos2(barrel.max.x, barrel.min.y - 4.0 * scale),
+            pos2(barrel.max.x, barrel.max.y + 4.0 * scale),
+        ],
+        Stroke::new(3.0 * scale, outline_color),
+    );
+
+    // Plunger stopper, rod and thumb press
+    painter.rect_filled(
+        Rect::from_min_max(
+            pos2(stopper_x, inner.min.y),
+            pos2(stopper_x + 4.0 * scale, inner.max.y),
+        ),
+        1.0 * scale,
+        outline_color,
+    );
+    let thumb_x = stopper_x + 44.0 * scale;
+    painter.line_segment(
+        [
+            pos2(stopper_x + 4.0 * scale, barrel.center().y),
+            pos2(thumb_x, barrel.center().y),
+        ],
+        Stroke::new(3.0 * scale, outline_color),
+    );
+    painter.line_segment(
+        [
+            pos2(thumb_x, barrel.min.y + 2.0 * scale),
+            pos2(thumb_x, barrel.max.y - 2.0 * scale),
+        ],
+        Stroke::new(4.0 * scale, outline_color),
+    );
+}
+
+/// Advance a dispense/withdraw button through its press-and-hold states.
+///
+/// ```held_for``` tracks how long the button has been pressed in seconds
+/// and is reset to zero on release. Repaints are requested while the
+/// button is down so that ```Held``` is reported every frame.
+fn update_button(
+    ui: &Ui,
+    response: &Response,
+    button: PumpButton,
+    state: &mut PumpDispenseWithdrawState,
+    held_for: &mut f32,
+    hold_threshold: f32,
+) -> Option<PumpEventKind> {
+    let was_held = *state == PumpDispenseWithdrawState::Held;
+    let was_pressed = *state == PumpDispenseWithdrawState::Pressed;
+
+    if !response.is_pointer_button_down_on() {
+        let released_after = *held_for;
+        *state = PumpDispenseWithdrawState::None;
+        *held_for = 0.0;
+        return if was_held {
+            Some(PumpEventKind::ButtonReleased {
+                button,
+                held_for: released_after,
+            })
+        } else if response.clicked() || (was_pressed && response.hovered()) {
+            // egui stops counting a press as a click after max_click_duration,
+            // any release over the button before the hold threshold is a click
+            Some(PumpEventKind::ButtonClicked(button))
+        } else {
+            None
+        };
+    }
 
-        // Create an interactable area over the image
-        let response = ui.interact(image_rect, Id::new(123), egui::Sense::click());
-        response
+    *held_for = ui.input(|i| {
+        i.pointer
+            .press_start_time()
+            .map_or(0.0, |start| (i.time - start) as f32)
+    });
+    ui.ctx().request_repaint();
+    if *held_for >= hold_threshold {
+        *state = PumpDispenseWithdrawState::Held;
+        Some(PumpEventKind::ButtonHeld {
+            button,
+            held_for: *held_for,
+        })
+    } else {
+        *state = PumpDispenseWithdrawState::Pressed;
+        None
+    }
+}
+
+/// Put a button back to ```None``` when it stops being usable.
+///
+/// A held button is reported as released, so whatever it started is stopped.
+fn reset_button(
+    button: PumpButton,
+    state: &mut PumpDispenseWithdrawState,
+    held_for: &mut f32,
+) -> Option<PumpEventKind> {
+    let released =
+        (*state == PumpDispenseWithdrawState::Held).then_some(PumpEventKind::ButtonReleased {
+            button,
+            held_for: *held_for,
+        });
+    *state = PumpDispenseWithdrawState::None;
+    *held_for = 0.0;
+    released
+}
+
+/// Register an interactable hotspot within the pump panel.
+///
+/// Must be called before the hotspot's icon is painted so that the
+/// hover highlight sits behind it. Disabled uis and overlapping
+/// layers are handled by egui's own hit testing.
+fn hotspot(
+    ui: &Ui,
+    painter: &Painter,
+    rect: Rect,
+    id: Id,
+    highlight: Color32,
+    (padding, rounding): (f32, f32),
+) -> Response {
+    let response = ui.interact(rect, id, Sense::click());
+    if !response.enabled() {
+        return response;
+    }
+    if response.hovered() {
+        let alpha = if response.is_pointer_button_down_on() {
+            0.35
+        } else {
+            0.2
+        };
+        painter.rect_filled(
+            rect.expand(padding),
+            rounding,
+            highlight.gamma_multiply(alpha),
+        );
     }
+    response.on_hover_cursor(CursorIcon::PointingHand)
 }
 
 // ==================================================================
@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+use std::fmt;
+use web_time::Instant;
+
+use egui::Color32;
+
+/// What the pump is doing
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RunState {
+    #[default]
+    Idle,
+    Running,
+    Paused,
+    /// The motor stalled or the line is blocked
+    Stalled,
+    /// The plunger reached the end of the barrel
+    EndOfStroke,
+    /// A fault that must be cleared by returning to ```Idle```
+    Error,
+}
+
+/// Status LED colours for ```Idle``` to ```Error```, in declaration order
+pub const DEFAULT_LED_COLORS: [Color32; 6] = [
+    Color32::from_gray(128),         // Idle
+    Color32::from_rgb(46, 204, 64),  // Running
+    Color32::from_rgb(255, 190, 0),  // Paused
+    Color32::from_rgb(255, 120, 0),  // Stalled
+    Color32::from_rgb(60, 140, 255), // EndOfStroke
+    Color32::from_rgb(230, 40, 40),  // Error
+];
+
+impl RunState {
+    pub const ALL: [RunState; 6] = [
+        RunState::Idle,
+        RunState::Running,
+        RunState::Paused,
+        RunState::Stalled,
+        RunState::EndOfStroke,
+        RunState::Error,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunState::Idle => "Idle",
+            RunState::Running => "Running",
+            RunState::Paused => "Paused",
+            RunState::Stalled => "Stalled",
+            RunState::EndOfStroke => "End of stroke",
+            RunState::Error => "Error",
+        }
+    }
+
+    /// LED colour for this state from a list laid out like ```DEFAULT_LED_COLORS```
+    pub fn color(&self, led_colors: &[Color32; 6]) -> Color32 {
+        led_colors[*self as usize]
+    }
+
+    /// Whether the pump may go straight from this state to ```next```
+    ///
+    /// Any state may return to ```Idle``` and any state may fail into
+    /// ```Error```. Otherwise a pump runs from ```Idle```, pauses and
+    /// resumes, and stops by itself on a stall or at the end of the
+    /// stroke. A run may restart from the end of the stroke, e.g. in
+    /// the other direction, but a stall must be cleared through ```Idle```.
+    pub fn can_transition_to(&self, next: RunState) -> bool {
+        use RunState::*;
+        match (*self, next) {
+            (_, Idle) | (_, Error) => true,
+            (Error, _) => false,
+            (Idle | Paused | EndOfStroke, Running) => true,
+            (Running, Paused | Stalled | EndOfStroke) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RunState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// A change of ```RunState```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunStateTransition {
+    pub from: RunState,
+    pub to: RunState,
+    pub at: Instant,
+}
+
+/// A transition refused by ```RunStateLog::set```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalTransition {
+    pub from: RunState,
+    pub to: RunState,
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pump cannot go from {} to {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
+/// Current ```RunState``` with the recent transitions that led to it
+///
+/// Only legal transitions are accepted, see ```RunState::can_transition_to```.
+/// Setting the current state again is accepted and not recorded.
+///
+/// States reported by a pump are tracked with ```follow```, which may
+/// record an extra transition through ```Idle```, so the history can
+/// show e.g. Stalled, Idle, Running for a pump that went straight from
+/// a stall back to running.
+#[derive(Default, Debug, Clone)]
+pub struct RunStateLog {
+    state: RunState,
+    history: VecDeque<RunStateTransition>,
+}
+
+impl RunStateLog {
+    /// Transitions kept in ```history```, oldest are dropped first
+    pub const HISTORY_LEN: usize = 64;
+
+    pub fn state(&self) -> RunState {
+        self.state
+    }
+
+    /// Move to ```next``` now.
+    pub fn set(&mut self, next: RunState) -> Result<(), IllegalTransition> {
+        self.set_at(next, Instant::now())
+    }
+
+    /// Move to ```next```, recording the transition as happening ```at```.
+    pub fn set_at(&mut self, next: RunState, at: Instant) -> Result<(), IllegalTransition> {
+        if next == self.state {
+            return Ok(());
+        }
+        if !self.state.can_transition_to(next) {
+            return Err(IllegalTransition {
+                from: self.state,
+                to: next,
+            });
+        }
+        if self.history.len() == Self::HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(RunStateTransition {
+            from: self.state,
+            to: next,
+            at,
+        });
+        self.state = next;
+        Ok(())
+    }
+
+    /// Track a state reported by a pump or simulator.
+    ///
+    /// Passes through ```Idle``` when ```next``` cannot be reached
+    /// directly, e.g. a stall that the pump has cleared by itself.
+    pub fn follow(&mut self, next: RunState) {
+        let now = Instant::now();
+        if self.set_at(next, now).is_err() {
+            let _ = self.set_at(RunState::Idle, now);
+            let _ = self.set_at(next, now);
+        }
+    }
+
+    /// When the current state was entered, ```None``` if it never changed
+    pub fn since(&self) -> Option<Instant> {
+        self.history.back().map(|transition| transition.at)
+    }
+
+    /// Recent transitions, oldest first
+    pub fn history(&self) -> &VecDeque<RunStateTransition> {
+        &self.history
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use RunState::*;
+
+    #[test]
+    fn transitions() {
+        for state in RunState::ALL {
+            assert!(state.can_transition_to(Idle));
+            assert!(state.can_transition_to(Error));
+        }
+        assert!(Idle.can_transition_to(Running));
+        assert!(Paused.can_transition_to(Running));
+        assert!(EndOfStroke.can_transition_to(Running));
+        assert!(Running.can_transition_to(Paused));
+        assert!(Running.can_transition_to(Stalled));
+        assert!(Running.can_transition_to(EndOfStroke));
+        assert!(!Stalled.can_transition_to(Running));
+        assert!(!Error.can_transition_to(Running));
+        assert!(!Idle.can_transition_to(Paused));
+        assert!(!Idle.can_transition_to(Stalled));
+        assert!(!Paused.can_transition_to(EndOfStroke));
+    }
+
+    #[test]
+    fn set_at_rejects_illegal() {
+        let start = Instant::now();
+        let mut log = RunStateLog::default();
+        assert_eq!(
+            log.set_at(Paused, start),
+            Err(IllegalTransition {
+                from: Idle,
+                to: Paused
+            })
+        );
+        assert_eq!(log.state(), Idle);
+        assert!(log.history().is_empty());
+        assert_eq!(log.since(), None);
+
+        let later = start + Duration::from_secs(1);
+        log.set_at(Running, later).unwrap();
+        log.set_at(Running, later + Duration::from_secs(1)).unwrap();
+        assert_eq!(log.history().len(), 1);
+        assert_eq!(log.since(), Some(later));
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let start = Instant::now();
+        let mut log = RunStateLog::default();
+        for i in 0..RunStateLog::HISTORY_LEN as u64 {
+            let next = if log.state() == Idle { Running } else { Idle };
+            log.set_at(next, start + Duration::from_secs(i)).unwrap();
+        }
+        assert_eq!(log.history().len(), RunStateLog::HISTORY_LEN);
+        log.set_at(Error, start).unwrap();
+        assert_eq!(log.history().len(), RunStateLog::HISTORY_LEN);
+        assert_eq!(log.history()[0].from, Running);
+        assert_eq!(log.history().back().unwrap().to, Error);
+
+        log.clear_history();
+        assert!(log.history().is_empty());
+        assert_eq!(log.state(), Error);
+    }
+
+    #[test]
+    fn follow_goes_through_idle() {
+        let mut log = RunStateLog::default();
+        log.follow(Running);
+        log.follow(Stalled);
+        log.follow(Running);
+        assert_eq!(log.state(), Running);
+        let states: Vec<_> = log.history().iter().map(|t| (t.from, t.to)).collect();
+        assert_eq!(
+            states,
+            [
+                (Idle, Running),
+                (Running, Stalled),
+                (Stalled, Idle),
+                (Idle, Running)
+            ]
+        );
+    }
+}
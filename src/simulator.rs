@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use egui::Ui;
+
+use crate::{
+    FlowDirection, FlowRate, PumpButton, PumpData, PumpEventKind, RunState, SyringeSpec, Volume,
+};
+
+/// A syringe pump with no hardware behind it
+///
+/// Moves the plunger at the set flow rate while running and stops
+/// by itself at either end of the stroke. Time is supplied by the
+/// caller, either per frame with ```update``` or with ```advance```
+/// from any clock, so runs can be replayed at a fixed step.
+///
+/// The barrel starts full, ready to dispense.
+#[derive(Debug, Clone)]
+pub struct PumpSimulator {
+    spec: SyringeSpec,
+    flow_rate: FlowRate,
+    direction: FlowDirection,
+    running: bool,
+    end_of_stroke: bool,
+    target: Option<Volume>,
+    run_moved: Volume,
+    volume: Volume,
+    dispensed: Volume,
+    withdrawn: Volume,
+}
+
+impl PumpSimulator {
+    pub fn new(spec: SyringeSpec) -> Self {
+        PumpSimulator {
+            volume: spec.capacity(),
+            spec,
+            flow_rate: FlowRate::ZERO,
+            direction: FlowDirection::Dispense,
+            running: false,
+            end_of_stroke: false,
+            target: None,
+            run_moved: Volume::ZERO,
+            dispensed: Volume::ZERO,
+            withdrawn: Volume::ZERO,
+        }
+    }
+
+    pub fn spec(&self) -> &SyringeSpec {
+        &self.spec
+    }
+
+    /// Fit a different syringe, keeping the current volume where it fits.
+    pub fn set_spec(&mut self, spec: SyringeSpec) {
+        self.volume = self.volume.min(spec.capacity());
+        self.spec = spec;
+    }
+
+    pub fn flow_rate(&self) -> FlowRate {
+        self.flow_rate
+    }
+
+    pub fn set_flow_rate(&mut self, flow_rate: FlowRate) {
+        self.flow_rate = flow_rate;
+    }
+
+    pub fn direction(&self) -> FlowDirection {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: FlowDirection) {
+        self.direction = direction;
+    }
+
+    /// Volume to move in each run before stopping, ```None``` to run
+    /// to the end of the stroke
+    pub fn set_target(&mut self, target: Option<Volume>) {
+        self.target = target;
+    }
+
+    pub fn target(&self) -> Option<Volume> {
+        self.target
+    }
+
+    /// Start moving the plunger, unless already at the end of the stroke.
+    pub fn start(&mut self) {
+        self.end_of_stroke = self.stroke_left() <= Volume::ZERO;
+        self.running = !self.end_of_stroke;
+        self.run_moved = Volume::ZERO;
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The last run stopped because the plunger reached the end of the barrel
+    pub fn at_end_of_stroke(&self) -> bool {
+        self.end_of_stroke
+    }
+
+    /// Liquid in the barrel
+    pub fn volume(&self) -> Volume {
+        self.volume
+    }
+
+    /// Set the liquid in the barrel, e.g. after swapping syringes.
+    pub fn fill(&mut self, volume: Volume) {
+        self.volume = volume.clamp(Volume::ZERO, self.spec.capacity());
+        self.end_of_stroke = false;
+    }
+
+    /// 0.0 when empty, 1.0 when full
+    pub fn fraction_full(&self) -> f32 {
+        (self.volume / self.spec.capacity()).clamp(0.0, 1.0)
+    }
+
+    /// Plunger distance from fully pushed in, in mm
+    pub fn plunger_position_mm(&self) -> f32 {
+        self.spec.displacement_mm(self.volume)
+    }
+
+    /// Total dispensed since created or ```reset_totals```
+    pub fn dispensed(&self) -> Volume {
+        self.dispensed
+    }
+
+    /// Total withdrawn since created or ```reset_totals```
+    pub fn withdrawn(&self) -> Volume {
+        self.withdrawn
+    }
+
+    pub fn reset_totals(&mut self) {
+        self.dispensed = Volume::ZERO;
+        self.withdrawn = Volume::ZERO;
+    }
+
+    /// Volume that can still move in the current direction
+    fn stroke_left(&self) -> Volume {
+        match self.direction {
+            FlowDirection::Dispense => self.volume,
+            FlowDirection::Withdraw => self.spec.capacity() - self.volume,
+        }
+    }
+
+    /// Move the plunger for ```dt``` and return the volume moved.
+    ///
+    /// Stops at the end of the stroke or on reaching the target, so
+    /// less than ```flow_rate * dt``` may be moved.
+    pub fn advance(&mut self, dt: Duration) -> Volume {
+        if !self.running {
+            return Volume::ZERO;
+        }
+        let stroke_left = self.stroke_left().max(Volume::ZERO);
+        let target_left = self.target.map_or(stroke_left, |target| {
+            (target - self.run_moved).max(Volume::ZERO)
+        });
+        let left = stroke_left.min(target_left);
+        let moved = (self.flow_rate * dt).abs().min(left);
+        if moved >= left {
+            self.running = false;
+            self.end_of_stroke = moved >= stroke_left;
+        }
+        self.run_moved += moved;
+        match self.direction {
+            FlowDirection::Dispense => {
+                self.volume -= moved;
+                self.dispensed += moved;
+            }
+            FlowDirection::Withdraw => {
+                self.volume += moved;
+                self.withdrawn += moved;
+            }
+        }
+        moved
+    }
+
+    /// Advance by the frame time, repainting while running.
+    pub fn update(&mut self, ui: &Ui) -> Volume {
+        let dt = ui.input(|i| i.stable_dt);
+        let moved = self.advance(Duration::from_secs_f32(dt.max(0.0)));
+        if self.running {
+            ui.ctx().request_repaint();
+        }
+        moved
+    }
+
+    /// Drive the simulator from the pump's dispense/withdraw buttons.
+    ///
+    /// A click starts a run in that direction, or stops it if it is
+    /// already running that way. Holding a button runs until release.
+    pub fn handle_event(&mut self, kind: &PumpEventKind) {
+        let direction = PumpButton::direction;
+        match kind {
+            PumpEventKind::ButtonClicked(button) => {
+                if self.running && self.direction == direction(button) {
+                    self.stop();
+                } else {
+                    self.direction = direction(button);
+                    self.start();
+                }
+            }
+            PumpEventKind::ButtonHeld { button, .. }
+                if !self.running || self.direction != direction(button) =>
+            {
+                self.direction = direction(button);
+                self.start();
+            }
+            PumpEventKind::ButtonReleased { .. } => self.stop(),
+            _ => {}
+        }
+    }
+
+    /// Run state matching the simulator: running, stopped at the end
+    /// of the stroke or idle
+    pub fn run_state(&self) -> RunState {
+        if self.running {
+            RunState::Running
+        } else if self.end_of_stroke {
+            RunState::EndOfStroke
+        } else {
+            RunState::Idle
+        }
+    }
+
+    /// Copy flow rate, barrel volume, run state and totals into ```pump```
+    /// for display.
+    ///
+    /// A pump put in an error state by the host stays there until the
+    /// host clears it.
+    pub fn apply(&self, pump: &mut PumpData) {
+        pump.flow_rate = self.flow_rate;
+        pump.volume = self.volume;
+        pump.direction = self.direction;
+        if pump.run_state.state() != RunState::Error {
+            pump.run_state.follow(self.run_state());
+        }
+        pump.dispensed = self.dispensed;
+        pump.withdrawn = self.withdrawn;
+    }
+}
@@ -0,0 +1,449 @@
+use std::borrow::Cow;
+use std::f32::consts::PI;
+use std::io;
+use std::path::Path;
+
+use crate::{Syringeset, Volume};
+use SyringeMaterial::{Glass, Plastic};
+
+/// What the syringe barrel is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyringeMaterial {
+    Glass,
+    Plastic,
+    StainlessSteel,
+}
+
+impl SyringeMaterial {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SyringeMaterial::Glass => "Glass",
+            SyringeMaterial::Plastic => "Plastic",
+            SyringeMaterial::StainlessSteel => "Stainless steel",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        [Glass, Plastic, SyringeMaterial::StainlessSteel]
+            .into_iter()
+            .find(|material| material.label().eq_ignore_ascii_case(label))
+    }
+}
+
+/// Physical dimensions of a syringe
+///
+/// Converts between dispensed volume and plunger travel. Lengths
+/// are in mm, and 1 mm of travel displaces ```area_mm2``` uL.
+///
+/// The built-in table in ```BUILTIN_SYRINGES``` covers common
+/// Hamilton, BD, Terumo and SGE syringes. Other syringes can be
+/// described with ```SyringeSpec::custom```.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyringeSpec {
+    pub manufacturer: Cow<'static, str>,
+    pub model: Cow<'static, str>,
+    /// Nominal volume printed on the barrel
+    pub volume: Volume,
+    pub inner_diameter_mm: f32,
+    pub max_stroke_mm: f32,
+    /// Volume left in the hub and needle with the plunger fully in
+    pub dead_volume: Volume,
+    pub material: SyringeMaterial,
+}
+
+impl SyringeSpec {
+    /// A syringe that is not in the built-in table
+    ///
+    /// The stroke is worked out from the nominal volume, use
+    /// ```max_stroke_mm``` if the barrel holds more. Dead volume
+    /// defaults to 0 uL and material to glass.
+    ///
+    /// # Panics
+    ///
+    /// If ```inner_diameter_mm``` is not a positive number.
+    pub fn custom(
+        manufacturer: impl Into<Cow<'static, str>>,
+        model: impl Into<Cow<'static, str>>,
+        volume: Volume,
+        inner_diameter_mm: f32,
+    ) -> Self {
+        assert!(
+            inner_diameter_mm > 0.0 && inner_diameter_mm.is_finite(),
+            "syringe inner diameter must be positive, got {inner_diameter_mm}"
+        );
+        SyringeSpec {
+            manufacturer: manufacturer.into(),
+            model: model.into(),
+            ..SyringeSpec::catalog("", "", volume.as_ul(), inner_diameter_mm, 0.0, Glass)
+        }
+    }
+
+    pub fn max_stroke_mm(mut self, max_stroke_mm: f32) -> Self {
+        self.max_stroke_mm = max_stroke_mm;
+        self
+    }
+
+    pub fn dead_volume(mut self, dead_volume: Volume) -> Self {
+        self.dead_volume = dead_volume;
+        self
+    }
+
+    pub fn material(mut self, material: SyringeMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Table entry with the stroke worked out from the nominal volume
+    const fn catalog(
+        manufacturer: &'static str,
+        model: &'static str,
+        volume_ul: f32,
+        inner_diameter_mm: f32,
+        dead_volume_ul: f32,
+        material: SyringeMaterial,
+    ) -> Self {
+        SyringeSpec {
+            manufacturer: Cow::Borrowed(manufacturer),
+            model: Cow::Borrowed(model),
+            volume: Volume::from_ul(volume_ul),
+            inner_diameter_mm,
+            max_stroke_mm: volume_ul / (PI / 4.0 * inner_diameter_mm * inner_diameter_mm),
+            dead_volume: Volume::from_ul(dead_volume_ul),
+            material,
+        }
+    }
+
+    /// Plunger cross-section in mm², equal to uL per mm of travel
+    pub fn area_mm2(&self) -> f32 {
+        PI / 4.0 * self.inner_diameter_mm * self.inner_diameter_mm
+    }
+
+    /// Volume with the plunger fully drawn back
+    pub fn capacity(&self) -> Volume {
+        self.volume_for(self.max_stroke_mm)
+    }
+
+    /// Plunger travel in mm needed to move ```volume```
+    pub fn displacement_mm(&self, volume: Volume) -> f32 {
+        volume.as_ul() / self.area_mm2()
+    }
+
+    /// Volume moved by ```displacement_mm``` of plunger travel
+    pub fn volume_for(&self, displacement_mm: f32) -> Volume {
+        Volume::from_ul(displacement_mm * self.area_mm2())
+    }
+
+    /// "Manufacturer model", as shown in the syringe picker
+    pub fn label(&self) -> String {
+        format!("{} {}", self.manufacturer, self.model)
+    }
+}
+
+// ====================================================================
+// Built-in syringes
+// ====================================================================
+
+/// Common syringes, smallest first within each manufacturer
+///
+/// Volumes and diameters are taken from the manufacturers' data
+/// sheets. The stroke is worked out from the two, so it is the travel
+/// over the graduated volume, e.g. about 54 mm for a Hamilton 1701,
+/// not the full barrel length.
+pub static BUILTIN_SYRINGES: [SyringeSpec; 27] = [
+    SyringeSpec::catalog("Hamilton", "1701", 10.0, 0.485, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1702", 25.0, 0.729, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1705", 50.0, 1.03, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1710", 100.0, 1.457, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1725", 250.0, 2.304, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1750", 500.0, 3.256, 0.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1001", 1000.0, 4.608, 2.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1002", 2500.0, 7.286, 2.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1005", 5000.0, 10.3, 5.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1010", 10000.0, 14.567, 5.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1025", 25000.0, 23.033, 10.0, Glass),
+    SyringeSpec::catalog("Hamilton", "1050", 50000.0, 32.573, 10.0, Glass),
+    SyringeSpec::catalog("BD", "Plastipak 1 mL", 1000.0, 4.78, 70.0, Plastic),
+    SyringeSpec::catalog("BD", "Plastipak 3 mL", 3000.0, 8.66, 80.0, Plastic),
+    SyringeSpec::catalog("BD", "Plastipak 5 mL", 5000.0, 12.06, 90.0, Plastic),
+    SyringeSpec::catalog("BD", "Plastipak 10 mL", 10000.0, 14.5, 100.0, Plastic),
+    SyringeSpec::catalog("BD", "Plastipak 20 mL", 20000.0, 19.13, 110.0, Plastic),
+    SyringeSpec::catalog("BD", "Plastipak 60 mL", 60000.0, 26.59, 150.0, Plastic),
+    SyringeSpec::catalog("Terumo", "1 mL", 1000.0, 4.7, 70.0, Plastic),
+    SyringeSpec::catalog("Terumo", "3 mL", 3000.0, 8.95, 80.0, Plastic),
+    SyringeSpec::catalog("Terumo", "5 mL", 5000.0, 13.0, 90.0, Plastic),
+    SyringeSpec::catalog("Terumo", "10 mL", 10000.0, 15.8, 100.0, Plastic),
+    SyringeSpec::catalog("Terumo", "20 mL", 20000.0, 20.15, 110.0, Plastic),
+    SyringeSpec::catalog("Terumo", "60 mL", 60000.0, 29.7, 150.0, Plastic),
+    SyringeSpec::catalog("SGE", "10R-GT", 10.0, 0.46, 0.0, Glass),
+    SyringeSpec::catalog("SGE", "25ML-LL-GT", 25000.0, 23.0, 10.0, Glass),
+    SyringeSpec::catalog("SGE", "100ML-LL-GT", 100000.0, 35.0, 20.0, Glass),
+];
+
+/// Look up a syringe in ```BUILTIN_SYRINGES``` by manufacturer and model.
+pub fn builtin_syringe(manufacturer: &str, model: &str) -> Option<&'static SyringeSpec> {
+    BUILTIN_SYRINGES
+        .iter()
+        .find(|spec| spec.manufacturer == manufacturer && spec.model == model)
+}
+
+impl Syringeset {
+    /// Built-in syringe for this nominal volume
+    ///
+    /// ```Custom``` volumes have no built-in spec, see ```SyringeLibrary::spec```.
+    /// Hamilton gastight syringes where available, BD for 3 mL and SGE
+    /// for 100 mL.
+    pub fn spec(&self) -> Option<&'static SyringeSpec> {
+        let (manufacturer, model) = match self {
+            Syringeset::None => return None,
+            Syringeset::UL10 => ("Hamilton", "1701"),
+            Syringeset::UL25 => ("Hamilton", "1702"),
+            Syringeset::UL50 => ("Hamilton", "1705"),
+            Syringeset::UL100 => ("Hamilton", "1710"),
+            Syringeset::UL250 => ("Hamilton", "1725"),
+            Syringeset::UL500 => ("Hamilton", "1750"),
+            Syringeset::UL1000 => ("Hamilton", "1001"),
+            Syringeset::UL2500 => ("Hamilton", "1002"),
+            Syringeset::UL3000 => ("BD", "Plastipak 3 mL"),
+            Syringeset::UL5000 => ("Hamilton", "1005"),
+            Syringeset::UL10000 => ("Hamilton", "1010"),
+            Syringeset::UL25000 => ("Hamilton", "1025"),
+            Syringeset::UL50000 => ("Hamilton", "1050"),
+            Syringeset::UL100000 => ("SGE", "100ML-LL-GT"),
+            Syringeset::Custom(_) => return None,
+        };
+        builtin_syringe(manufacturer, model)
+    }
+}
+
+// ====================================================================
+// User-defined syringes
+// ====================================================================
+
+/// User-defined syringes offered by the syringe picker
+///
+/// Syringes are told apart by manufacturer and model. Picking one
+/// sets ```PumpData::custom_syringe``` to its spec, so several
+/// syringes of the same volume can be offered side by side.
+///
+/// The library is saved as tab-separated text, one syringe per line:
+/// manufacturer, model, volume (uL), inner diameter (mm), max stroke
+/// (mm), dead volume (uL) and material. Blank lines and lines starting
+/// with ```#``` are ignored, so a manufacturer starting with ```#``` or
+/// a backslash is saved with a backslash in front.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyringeLibrary {
+    syringes: Vec<SyringeSpec>,
+}
+
+impl SyringeLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a syringe, replacing one with the same manufacturer and model.
+    pub fn add(&mut self, spec: SyringeSpec) {
+        match self
+            .syringes
+            .iter_mut()
+            .find(|s| s.manufacturer == spec.manufacturer && s.model == spec.model)
+        {
+            Some(existing) => *existing = spec,
+            None => self.syringes.push(spec),
+        }
+    }
+
+    pub fn remove(&mut self, manufacturer: &str, model: &str) -> Option<SyringeSpec> {
+        let index = self
+            .syringes
+            .iter()
+            .position(|s| s.manufacturer == manufacturer && s.model == model)?;
+        Some(self.syringes.remove(index))
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, SyringeSpec> {
+        self.syringes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.syringes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.syringes.is_empty()
+    }
+
+    /// The syringe with this manufacturer and model
+    pub fn get(&self, manufacturer: &str, model: &str) -> Option<&SyringeSpec> {
+        self.syringes
+            .iter()
+            .find(|s| s.manufacturer == manufacturer && s.model == model)
+    }
+
+    /// Spec for ```syringeset```
+    ///
+    /// ```Custom``` volumes are looked up in the library, standard
+    /// volumes in the built-in table. Of several library syringes with
+    /// the same volume the first is returned, use ```get``` to pick one.
+    pub fn spec(&self, syringeset: Syringeset) -> Option<&SyringeSpec> {
+        match syringeset {
+            Syringeset::Custom(volume) => self.syringes.iter().find(|s| s.volume == volume),
+            _ => syringeset.spec(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let field = |text: &str| text.replace(['\t', '\n'], " ");
+        let escape = |text: String| {
+            if text.trim_start().starts_with(['#', '\\']) {
+                format!("\\{text}")
+            } else {
+                text
+            }
+        };
+        let mut text = String::from(
+            "# manufacturer\tmodel\tvolume_ul\tinner_diameter_mm\tmax_stroke_mm\tdead_volume_ul\tmaterial\n",
+        );
+        for spec in &self.syringes {
+            text += &format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                escape(field(&spec.manufacturer)),
+                field(&spec.model),
+                spec.volume.as_ul(),
+                spec.inner_diameter_mm,
+                spec.max_stroke_mm,
+                spec.dead_volume.as_ul(),
+                spec.material.label(),
+            );
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Self> {
+        let mut library = SyringeLibrary::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |what: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("syringe library line {}: {what}", number + 1),
+                )
+            };
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let [manufacturer, model, volume, diameter, stroke, dead_volume, material] = fields[..]
+            else {
+                return Err(invalid("expected 7 tab-separated fields"));
+            };
+            let manufacturer = manufacturer.strip_prefix('\\').unwrap_or(manufacturer);
+            let number = |field: &str, what: &str| {
+                field
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite() && *value >= 0.0)
+                    .ok_or_else(|| invalid(what))
+            };
+            let diameter = number(diameter, "bad inner diameter")?;
+            if diameter == 0.0 {
+                return Err(invalid("inner diameter must be positive"));
+            }
+            library.add(SyringeSpec {
+                manufacturer: Cow::Owned(manufacturer.to_string()),
+                model: Cow::Owned(model.to_string()),
+                volume: Volume::from_ul(number(volume, "bad volume")?),
+                inner_diameter_mm: diameter,
+                max_stroke_mm: number(stroke, "bad max stroke")?,
+                dead_volume: Volume::from_ul(number(dead_volume, "bad dead volume")?),
+                material: SyringeMaterial::from_label(material)
+                    .ok_or_else(|| invalid("unknown material"))?,
+            });
+        }
+        Ok(library)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> SyringeLibrary {
+        let mut library = SyringeLibrary::new();
+        library.add(
+            SyringeSpec::custom("Acme", "Micro 5", Volume::from_ul(5.0), 0.343)
+                .dead_volume(Volume::from_ul(0.5))
+                .material(SyringeMaterial::StainlessSteel),
+        );
+        library.add(SyringeSpec::custom(
+            "#1 Syringes",
+            "Tab\tmodel",
+            Volume::from_ul(5.0),
+            0.4,
+        ));
+        library
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let library = library();
+        let text = library.to_text();
+        assert!(text.contains("\\#1 Syringes\tTab model\t"));
+
+        let loaded = SyringeLibrary::from_text(&text).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded.get("Acme", "Micro 5"),
+            library.get("Acme", "Micro 5")
+        );
+        let escaped = loaded.get("#1 Syringes", "Tab model").unwrap();
+        assert_eq!(escaped.inner_diameter_mm, 0.4);
+
+        // Both share a volume, the first one added is picked
+        let spec = loaded.spec(Syringeset::Custom(Volume::from_ul(5.0)));
+        assert_eq!(spec.unwrap().model, "Micro 5");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("syringes-{}.tsv", std::process::id()));
+        library().save(&path).unwrap();
+        let loaded = SyringeLibrary::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().to_text(), library().to_text());
+    }
+
+    #[test]
+    fn bad_input() {
+        let error = |text: &str| SyringeLibrary::from_text(text).unwrap_err().to_string();
+        assert!(error("Acme\tA\t10").contains("line 1: expected 7"));
+        assert!(error("\n# comment\nAcme\tA\t10\t0\t50\t0\tGlass").contains("line 3"));
+        assert!(error("Acme\tA\t10\t0\t50\t0\tGlass").contains("inner diameter"));
+        assert!(error("Acme\tA\t-10\t0.5\t50\t0\tGlass").contains("bad volume"));
+        assert!(error("Acme\tA\t10\tNaN\t50\t0\tGlass").contains("bad inner diameter"));
+        assert!(error("Acme\tA\t10\t0.5\t50\t0\tWood").contains("unknown material"));
+        assert!(SyringeLibrary::from_text("\n# comment\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "inner diameter")]
+    fn custom_needs_diameter() {
+        SyringeSpec::custom("Acme", "Flat", Volume::from_ul(10.0), 0.0);
+    }
+
+    #[test]
+    fn builtin_stroke() {
+        let spec = builtin_syringe("Hamilton", "1701").unwrap();
+        assert!((spec.max_stroke_mm - 54.1).abs() < 0.1);
+        assert!((spec.capacity().as_ul() - 10.0).abs() < 1e-3);
+    }
+}
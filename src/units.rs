@@ -0,0 +1,502 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Why a volume, flow rate or duration could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseUnitError {
+    Empty,
+    /// The number part, e.g. "2,5" in "2,5 mL"
+    BadNumber(String),
+    /// The unit part, e.g. "cc" in "2 cc"
+    UnknownUnit(String),
+}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseUnitError::Empty => write!(f, "no value given"),
+            ParseUnitError::BadNumber(number) => write!(f, "\"{number}\" is not a number"),
+            ParseUnitError::UnknownUnit(unit) => write!(f, "unknown unit \"{unit}\""),
+        }
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+/// Split "2.5 mL" into 2.5 and "mL", with or without the space
+fn split_value(text: &str) -> Result<(f32, &str), ParseUnitError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ParseUnitError::Empty);
+    }
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .unwrap_or(text.len());
+    // An exponent needs digits after it, so "2 mL" is not read as "2e"
+    let split = match text[..split].rfind(['e', 'E']) {
+        Some(e) if e + 1 == split => e,
+        _ => split,
+    };
+    let (number, unit) = text.split_at(split);
+    let value = number
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| ParseUnitError::BadNumber(number.to_string()))?;
+    Ok((value, unit.trim()))
+}
+
+/// Volume of one unit in uL, accepting u, µ (micro sign) and μ (Greek mu)
+fn volume_unit_ul(unit: &str) -> Option<f32> {
+    match unit {
+        "nL" | "nl" => Some(1e-3),
+        "uL" | "ul" | "µL" | "µl" | "μL" | "μl" => Some(1.0),
+        "mL" | "ml" => Some(1e3),
+        "L" | "l" => Some(1e6),
+        _ => None,
+    }
+}
+
+/// Length of one unit in minutes
+fn time_unit_min(unit: &str) -> Option<f32> {
+    match unit {
+        "s" | "sec" => Some(1.0 / 60.0),
+        "min" => Some(1.0),
+        "h" | "hr" => Some(60.0),
+        _ => None,
+    }
+}
+
+/// Format to at most three decimals, dropping trailing zeros
+fn trimmed(value: f32) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+// ====================================================================
+// Volume
+// ====================================================================
+
+/// A volume of liquid
+///
+/// Displayed with the largest of nL, µL, mL and L that keeps the
+/// value at 1 or above, e.g. "2.5 mL". Parsed from the same form,
+/// with "uL" accepted for µL.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Volume {
+    ul: f32,
+}
+
+impl Volume {
+    pub const ZERO: Volume = Volume { ul: 0.0 };
+
+    pub const fn from_nl(nl: f32) -> Self {
+        Volume { ul: nl / 1e3 }
+    }
+
+    pub const fn from_ul(ul: f32) -> Self {
+        Volume { ul }
+    }
+
+    pub const fn from_ml(ml: f32) -> Self {
+        Volume { ul: ml * 1e3 }
+    }
+
+    pub const fn as_nl(&self) -> f32 {
+        self.ul * 1e3
+    }
+
+    pub const fn as_ul(&self) -> f32 {
+        self.ul
+    }
+
+    pub const fn as_ml(&self) -> f32 {
+        self.ul / 1e3
+    }
+
+    pub fn abs(self) -> Self {
+        Volume { ul: self.ul.abs() }
+    }
+
+    pub fn min(self, other: Volume) -> Self {
+        Volume {
+            ul: self.ul.min(other.ul),
+        }
+    }
+
+    pub fn max(self, other: Volume) -> Self {
+        Volume {
+            ul: self.ul.max(other.ul),
+        }
+    }
+
+    pub fn clamp(self, min: Volume, max: Volume) -> Self {
+        Volume {
+            ul: self.ul.clamp(min.ul, max.ul),
+        }
+    }
+}
+
+impl fmt::Display for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ul = self.ul.abs();
+        let (value, unit) = if ul >= 1e6 {
+            (self.ul / 1e6, "L")
+        } else if ul >= 1e3 {
+            (self.ul / 1e3, "mL")
+        } else if ul >= 1.0 || ul == 0.0 {
+            (self.ul, "µL")
+        } else {
+            (self.ul * 1e3, "nL")
+        };
+        write!(f, "{} {unit}", trimmed(value))
+    }
+}
+
+impl FromStr for Volume {
+    type Err = ParseUnitError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_value(text)?;
+        let scale =
+            volume_unit_ul(unit).ok_or_else(|| ParseUnitError::UnknownUnit(unit.to_string()))?;
+        Ok(Volume::from_ul(value * scale))
+    }
+}
+
+impl Add for Volume {
+    type Output = Volume;
+
+    fn add(self, other: Volume) -> Volume {
+        Volume::from_ul(self.ul + other.ul)
+    }
+}
+
+impl Sub for Volume {
+    type Output = Volume;
+
+    fn sub(self, other: Volume) -> Volume {
+        Volume::from_ul(self.ul - other.ul)
+    }
+}
+
+impl AddAssign for Volume {
+    fn add_assign(&mut self, other: Volume) {
+        self.ul += other.ul;
+    }
+}
+
+impl SubAssign for Volume {
+    fn sub_assign(&mut self, other: Volume) {
+        self.ul -= other.ul;
+    }
+}
+
+impl Neg for Volume {
+    type Output = Volume;
+
+    fn neg(self) -> Volume {
+        Volume::from_ul(-self.ul)
+    }
+}
+
+impl Mul<f32> for Volume {
+    type Output = Volume;
+
+    fn mul(self, factor: f32) -> Volume {
+        Volume::from_ul(self.ul * factor)
+    }
+}
+
+/// Ratio of two volumes, e.g. how full a syringe is
+impl Div for Volume {
+    type Output = f32;
+
+    fn div(self, other: Volume) -> f32 {
+        self.ul / other.ul
+    }
+}
+
+/// Average flow rate that moves this volume in the given time
+impl Div<Duration> for Volume {
+    type Output = FlowRate;
+
+    fn div(self, time: Duration) -> FlowRate {
+        FlowRate::from_ul_per_min(self.ul / (time.as_secs_f32() / 60.0))
+    }
+}
+
+// ====================================================================
+// Flow rate
+// ====================================================================
+
+/// A volumetric flow rate
+///
+/// Displayed in nL/min, µL/min or mL/min, e.g. "10 µL/min". Parsed
+/// from any volume unit per s, min or h, e.g. "2 mL/h".
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct FlowRate {
+    ul_per_min: f32,
+}
+
+impl FlowRate {
+    pub const ZERO: FlowRate = FlowRate { ul_per_min: 0.0 };
+
+    pub const fn from_nl_per_min(nl_per_min: f32) -> Self {
+        FlowRate {
+            ul_per_min: nl_per_min / 1e3,
+        }
+    }
+
+    pub const fn from_ul_per_min(ul_per_min: f32) -> Self {
+        FlowRate { ul_per_min }
+    }
+
+    pub const fn from_ul_per_hour(ul_per_hour: f32) -> Self {
+        FlowRate {
+            ul_per_min: ul_per_hour / 60.0,
+        }
+    }
+
+    pub const fn from_ml_per_min(ml_per_min: f32) -> Self {
+        FlowRate {
+            ul_per_min: ml_per_min * 1e3,
+        }
+    }
+
+    pub const fn from_ml_per_hour(ml_per_hour: f32) -> Self {
+        FlowRate {
+            ul_per_min: ml_per_hour * 1e3 / 60.0,
+        }
+    }
+
+    pub const fn as_nl_per_min(&self) -> f32 {
+        self.ul_per_min * 1e3
+    }
+
+    pub const fn as_ul_per_min(&self) -> f32 {
+        self.ul_per_min
+    }
+
+    pub const fn as_ul_per_hour(&self) -> f32 {
+        self.ul_per_min * 60.0
+    }
+
+    pub const fn as_ml_per_min(&self) -> f32 {
+        self.ul_per_min / 1e3
+    }
+
+    pub const fn as_ml_per_hour(&self) -> f32 {
+        self.ul_per_min * 60.0 / 1e3
+    }
+
+    /// Volume moved at this rate in ```time```
+    pub fn volume_in(&self, time: Duration) -> Volume {
+        Volume::from_ul(self.ul_per_min * time.as_secs_f32() / 60.0)
+    }
+
+    /// Time taken to move ```volume``` at this rate, ```None``` when stopped
+    pub fn time_for(&self, volume: Volume) -> Option<Duration> {
+        let minutes = volume.as_ul() / self.ul_per_min;
+        (minutes.is_finite() && minutes >= 0.0).then(|| Duration::from_secs_f32(minutes * 60.0))
+    }
+}
+
+impl fmt::Display for FlowRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ul_per_min = self.ul_per_min.abs();
+        let (value, unit) = if ul_per_min >= 1e3 {
+            (self.ul_per_min / 1e3, "mL/min")
+        } else if ul_per_min >= 1.0 || ul_per_min == 0.0 {
+            (self.ul_per_min, "µL/min")
+        } else {
+            (self.ul_per_min * 1e3, "nL/min")
+        };
+        write!(f, "{} {unit}", trimmed(value))
+    }
+}
+
+impl FromStr for FlowRate {
+    type Err = ParseUnitError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_value(text)?;
+        let unknown = || ParseUnitError::UnknownUnit(unit.to_string());
+        let (volume_unit, time_unit) = unit.split_once('/').ok_or_else(unknown)?;
+        let ul = volume_unit_ul(volume_unit.trim()).ok_or_else(unknown)?;
+        let min = time_unit_min(time_unit.trim()).ok_or_else(unknown)?;
+        Ok(FlowRate::from_ul_per_min(value * ul / min))
+    }
+}
+
+impl Mul<f32> for FlowRate {
+    type Output = FlowRate;
+
+    fn mul(self, factor: f32) -> FlowRate {
+        FlowRate::from_ul_per_min(self.ul_per_min * factor)
+    }
+}
+
+impl Mul<Duration> for FlowRate {
+    type Output = Volume;
+
+    fn mul(self, time: Duration) -> Volume {
+        self.volume_in(time)
+    }
+}
+
+// ====================================================================
+// Duration
+// ====================================================================
+
+/// Format a duration for display, e.g. "250 ms", "12.5 s", "3 min 20 s"
+/// or "1 h 5 min".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f32();
+    let whole = duration.as_secs();
+    if secs < 1.0 {
+        format!("{} ms", trimmed(secs * 1e3))
+    } else if secs < 60.0 {
+        format!("{} s", trimmed(secs))
+    } else if whole < 3600 {
+        match whole % 60 {
+            0 => format!("{} min", whole / 60),
+            s => format!("{} min {s} s", whole / 60),
+        }
+    } else {
+        match whole % 3600 / 60 {
+            0 => format!("{} h", whole / 3600),
+            min => format!("{} h {min} min", whole / 3600),
+        }
+    }
+}
+
+/// Parse a duration in ms, s, min or h, e.g. "1.5 h" or "90s".
+pub fn parse_duration(text: &str) -> Result<Duration, ParseUnitError> {
+    let (value, unit) = split_value(text)?;
+    let secs = match unit {
+        "ms" => value / 1e3,
+        unit => {
+            value
+                * 60.0
+                * time_unit_min(unit)
+                    .ok_or_else(|| ParseUnitError::UnknownUnit(unit.to_string()))?
+        }
+    };
+    Duration::try_from_secs_f32(secs).map_err(|_| ParseUnitError::BadNumber(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-4 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn parse_volume() {
+        let ul = |text: &str| text.parse::<Volume>().unwrap().as_ul();
+        assert!(close(ul("2.5 mL"), 2500.0));
+        assert!(close(ul("2.5mL"), 2500.0));
+        assert!(close(ul(" 250 nl "), 0.25));
+        assert!(close(ul("1 L"), 1e6));
+        for text in ["10 uL", "10 µL", "10 μL", "10 ul", "10 µl", "10 μl"] {
+            assert!(close(ul(text), 10.0), "{text}");
+        }
+        assert_eq!("".parse::<Volume>(), Err(ParseUnitError::Empty));
+        assert_eq!(
+            "2 cc".parse::<Volume>(),
+            Err(ParseUnitError::UnknownUnit("cc".to_string()))
+        );
+        assert_eq!(
+            "mL".parse::<Volume>(),
+            Err(ParseUnitError::BadNumber(String::new()))
+        );
+    }
+
+    #[test]
+    fn parse_exponent() {
+        let ul = |text: &str| text.parse::<Volume>().unwrap().as_ul();
+        assert!(close(ul("1.5e3 uL"), 1500.0));
+        assert!(close(ul("2E-3 mL"), 2.0));
+        assert!(close(ul("1e3nL"), 1.0));
+        // A trailing "e" belongs to the unit, not the number
+        assert_eq!(
+            "3e uL".parse::<Volume>(),
+            Err(ParseUnitError::UnknownUnit("e uL".to_string()))
+        );
+        assert_eq!(
+            "1e99 uL".parse::<Volume>(),
+            Err(ParseUnitError::BadNumber("1e99".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_flow_rate() {
+        let ul_per_min = |text: &str| text.parse::<FlowRate>().unwrap().as_ul_per_min();
+        assert!(close(ul_per_min("10 uL/min"), 10.0));
+        assert!(close(ul_per_min("10 µL / min"), 10.0));
+        assert!(close(ul_per_min("10 μl/min"), 10.0));
+        assert!(close(ul_per_min("2 mL/h"), 2000.0 / 60.0));
+        assert!(close(ul_per_min("1 ul/s"), 60.0));
+        assert!(close(ul_per_min("500 nL/min"), 0.5));
+        assert_eq!(
+            "10 uL".parse::<FlowRate>(),
+            Err(ParseUnitError::UnknownUnit("uL".to_string()))
+        );
+        assert_eq!(
+            "10 uL/day".parse::<FlowRate>(),
+            Err(ParseUnitError::UnknownUnit("uL/day".to_string()))
+        );
+    }
+
+    #[test]
+    fn display_scales() {
+        assert_eq!(Volume::from_ul(2500.0).to_string(), "2.5 mL");
+        assert_eq!(Volume::from_ml(1500.0).to_string(), "1.5 L");
+        assert_eq!(Volume::from_ul(10.0).to_string(), "10 µL");
+        assert_eq!(Volume::from_ul(0.25).to_string(), "250 nL");
+        assert_eq!(Volume::from_ul(-2500.0).to_string(), "-2.5 mL");
+        assert_eq!(Volume::ZERO.to_string(), "0 µL");
+        assert_eq!(FlowRate::from_ul_per_min(1500.0).to_string(), "1.5 mL/min");
+        assert_eq!(FlowRate::from_ul_per_min(10.0).to_string(), "10 µL/min");
+        assert_eq!(FlowRate::from_nl_per_min(500.0).to_string(), "500 nL/min");
+        assert_eq!(FlowRate::ZERO.to_string(), "0 µL/min");
+
+        // What is displayed to three decimals parses back
+        let volume = Volume::from_ul(1234.0);
+        assert_eq!(volume.to_string().parse::<Volume>().unwrap(), volume);
+    }
+
+    #[test]
+    fn durations() {
+        let secs = |text: &str| parse_duration(text).unwrap().as_secs_f32();
+        assert!(close(secs("1.5 h"), 5400.0));
+        assert!(close(secs("90s"), 90.0));
+        assert!(close(secs("2 min"), 120.0));
+        assert!(close(secs("250 ms"), 0.25));
+        assert_eq!(
+            parse_duration("2 days"),
+            Err(ParseUnitError::UnknownUnit("days".to_string()))
+        );
+        assert_eq!(
+            parse_duration("-1 s"),
+            Err(ParseUnitError::BadNumber("-1".to_string()))
+        );
+        assert_eq!(parse_duration(" "), Err(ParseUnitError::Empty));
+
+        assert_eq!(format_duration(Duration::from_millis(250)), "250 ms");
+        assert_eq!(format_duration(Duration::from_millis(12_500)), "12.5 s");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2 min");
+        assert_eq!(format_duration(Duration::from_secs(200)), "3 min 20 s");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2 h");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1 h 5 min");
+    }
+}